@@ -0,0 +1,190 @@
+/*
+ * Promptly: show a prompt, sooner.
+ * Copyright (C) 2017  Terrence Cole
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+// A rule that, when it matches, switches the whole prompt to the alert theme.
+//
+//   path:/srv/prod  -- the cwd is /srv/prod or below it, by its real path or
+//                      through symlinks, as $PWD has it
+//   host:prod-*     -- the hostname matches the glob
+//   user:deploy     -- the username matches the glob
+//   root            -- the effective uid is 0
+//   switched        -- the shell runs as someone else, via sudo or su
+#[derive(Clone, Debug, PartialEq)]
+pub enum AlertRule {
+    Path(PathBuf),
+    Host(String),
+    User(String),
+    Root,
+    Switched,
+}
+
+pub struct AlertContext<'a> {
+    pub cwd: &'a Path,
+    pub logical_cwd: &'a Path,
+    pub hostname: &'a str,
+    pub username: &'a str,
+    pub effective_uid: u32,
+    pub original_user: Option<&'a str>,
+}
+
+impl AlertRule {
+    pub fn matches(&self, ctx: &AlertContext) -> bool {
+        match self {
+            AlertRule::Path(path) => ctx.cwd.starts_with(path) || ctx.logical_cwd.starts_with(path),
+            AlertRule::Host(pattern) => glob_match(pattern, ctx.hostname),
+            AlertRule::User(pattern) => glob_match(pattern, ctx.username),
+            AlertRule::Root => ctx.effective_uid == 0,
+            AlertRule::Switched => ctx.original_user.is_some(),
+        }
+    }
+
+    // Rules are checked in the order given; the first that matches wins.
+    pub fn find_first<'a>(rules: &'a [AlertRule], ctx: &AlertContext) -> Option<&'a AlertRule> {
+        rules.iter().find(|rule| rule.matches(ctx))
+    }
+}
+
+impl FromStr for AlertRule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "root" => return Ok(AlertRule::Root),
+            "switched" => return Ok(AlertRule::Switched),
+            _ => {}
+        }
+        match s.split_once(':') {
+            Some(("path", path)) if !path.is_empty() => Ok(AlertRule::Path(PathBuf::from(path))),
+            Some(("host", pattern)) if !pattern.is_empty() => {
+                Ok(AlertRule::Host(pattern.to_owned()))
+            }
+            Some(("user", pattern)) if !pattern.is_empty() => {
+                Ok(AlertRule::User(pattern.to_owned()))
+            }
+            _ => Err(format!(
+                "unknown alert rule '{}'; expected path:DIR, host:GLOB, user:GLOB, root or switched",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for AlertRule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AlertRule::Path(path) => write!(f, "path:{}", path.display()),
+            AlertRule::Host(pattern) => write!(f, "host:{}", pattern),
+            AlertRule::User(pattern) => write!(f, "user:{}", pattern),
+            AlertRule::Root => write!(f, "root"),
+            AlertRule::Switched => write!(f, "switched"),
+        }
+    }
+}
+
+// Shell-style matching of `*` and `?`; everything else is literal.
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<char>>();
+    let text = text.chars().collect::<Vec<char>>();
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, t));
+            p += 1;
+        } else if let Some((star_p, star_t)) = backtrack {
+            p = star_p + 1;
+            t = star_t + 1;
+            backtrack = Some((star_p, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn globs() {
+        assert!(glob_match("prod-*", "prod-db1"));
+        assert!(glob_match("*-db?", "prod-db1"));
+        assert!(glob_match("*", ""));
+        assert!(!glob_match("prod-*", "staging-db1"));
+        assert!(!glob_match("prod-db?", "prod-db"));
+    }
+
+    #[test]
+    fn first_match_wins() {
+        let rules = [
+            "host:dev-*",
+            "path:/etc",
+            "path:/srv/prod",
+            "switched",
+            "root",
+        ]
+        .iter()
+        .map(|s| s.parse::<AlertRule>().unwrap())
+        .collect::<Vec<AlertRule>>();
+        let ctx = AlertContext {
+            cwd: Path::new("/etc/nginx"),
+            logical_cwd: Path::new("/etc/nginx"),
+            hostname: "prod-web",
+            username: "root",
+            effective_uid: 0,
+            original_user: None,
+        };
+        assert_eq!(
+            AlertRule::find_first(&rules, &ctx),
+            Some(&AlertRule::Path(PathBuf::from("/etc")))
+        );
+        // /srv/prod is a symlink to /data/prod.
+        let ctx = AlertContext {
+            cwd: Path::new("/data/prod/app"),
+            logical_cwd: Path::new("/srv/prod/app"),
+            ..ctx
+        };
+        assert_eq!(
+            AlertRule::find_first(&rules, &ctx),
+            Some(&AlertRule::Path(PathBuf::from("/srv/prod")))
+        );
+        let ctx = AlertContext {
+            cwd: Path::new("/etcetera"),
+            logical_cwd: Path::new("/etcetera"),
+            ..ctx
+        };
+        assert_eq!(AlertRule::find_first(&rules, &ctx), Some(&AlertRule::Root));
+        let ctx = AlertContext {
+            original_user: Some("alice"),
+            ..ctx
+        };
+        assert_eq!(
+            AlertRule::find_first(&rules, &ctx),
+            Some(&AlertRule::Switched)
+        );
+    }
+}
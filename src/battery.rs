@@ -0,0 +1,222 @@
+/*
+ * Promptly: show a prompt, sooner.
+ * Copyright (C) 2017  Terrence Cole
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+use crate::layout::{Color, Div, Span};
+use std::{
+    fs::{read_dir, read_to_string},
+    path::Path,
+    time::Duration,
+};
+
+const POWER_SUPPLY_DIR: &str = "/sys/class/power_supply";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BatteryState {
+    Charging,
+    Discharging,
+    Full,
+    // Plugged in, but held below full to spare the cells.
+    NotCharging,
+}
+
+// One battery as the kernel reports it. Energy is in µWh and power in µW; some
+// drivers report charge (µAh) and current (µA) instead, which work the same way
+// for our purposes.
+#[derive(Debug, Default)]
+struct Supply {
+    status: String,
+    capacity: Option<u64>,
+    now: Option<u64>,
+    full: Option<u64>,
+    rate: Option<u64>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Battery {
+    pub percent: u64,
+    pub state: BatteryState,
+    pub remaining: Option<Duration>,
+}
+
+fn read_number(dir: &Path, names: &[&str]) -> Option<u64> {
+    names
+        .iter()
+        .find_map(|name| read_to_string(dir.join(name)).ok()?.trim().parse().ok())
+}
+
+// Mice and headsets show up here too, with a scope of Device; skip them.
+fn read_supply(dir: &Path) -> Option<Supply> {
+    if read_to_string(dir.join("type")).ok()?.trim() != "Battery" {
+        return None;
+    }
+    if read_to_string(dir.join("scope")).is_ok_and(|s| s.trim() == "Device") {
+        return None;
+    }
+    Some(Supply {
+        status: read_to_string(dir.join("status"))
+            .map(|s| s.trim().to_owned())
+            .unwrap_or_default(),
+        capacity: read_number(dir, &["capacity"]),
+        now: read_number(dir, &["energy_now", "charge_now"]),
+        full: read_number(dir, &["energy_full", "charge_full"]),
+        rate: read_number(dir, &["power_now", "current_now"]),
+    })
+}
+
+pub fn find_battery() -> Option<Battery> {
+    let supplies = read_dir(POWER_SUPPLY_DIR)
+        .ok()?
+        .flatten()
+        .filter_map(|entry| read_supply(&entry.path()))
+        .collect::<Vec<Supply>>();
+    summarize(&supplies)
+}
+
+// Laptops with two batteries drain them one at a time, so treat them as one big
+// battery: add up what is left and what could be, and charge if either does.
+fn summarize(supplies: &[Supply]) -> Option<Battery> {
+    if supplies.is_empty() {
+        return None;
+    }
+    let has = |status: &str| supplies.iter().any(|s| s.status == status);
+    let state = if has("Discharging") {
+        BatteryState::Discharging
+    } else if has("Charging") {
+        BatteryState::Charging
+    } else if supplies.iter().all(|s| s.status == "Full") {
+        BatteryState::Full
+    } else {
+        BatteryState::NotCharging
+    };
+
+    let energies = supplies
+        .iter()
+        .map(|s| Some((s.now?, s.full?)))
+        .collect::<Option<Vec<(u64, u64)>>>();
+    let (now, full) = energies
+        .map(|e| e.iter().fold((0, 0), |(n, f), (sn, sf)| (n + sn, f + sf)))
+        .unwrap_or((0, 0));
+    let percent = if full > 0 {
+        (now * 100 / full).min(100)
+    } else {
+        let capacities = supplies
+            .iter()
+            .filter_map(|s| s.capacity)
+            .collect::<Vec<u64>>();
+        if capacities.is_empty() {
+            return None;
+        }
+        capacities.iter().sum::<u64>() / capacities.len() as u64
+    };
+
+    let rate = supplies.iter().filter_map(|s| s.rate).sum::<u64>();
+    let remaining = match state {
+        _ if rate == 0 || full == 0 => None,
+        BatteryState::Discharging => Some(now),
+        BatteryState::Charging => Some(full.saturating_sub(now)),
+        _ => None,
+    }
+    .map(|energy| Duration::from_secs(energy * 3600 / rate));
+
+    Some(Battery {
+        percent,
+        state,
+        remaining,
+    })
+}
+
+// Only worth the space when it could run out: on battery, or low either way.
+pub fn should_show(battery: &Battery, threshold: u64) -> bool {
+    battery.state == BatteryState::Discharging || battery.percent <= threshold
+}
+
+pub fn format_battery(battery: &Battery) -> Div {
+    let color = match battery.percent {
+        p if p > 50 => Color::Green,
+        p if p > 20 => Color::Yellow,
+        _ => Color::Red,
+    };
+    let icon = match battery.state {
+        BatteryState::Charging => "▴",
+        BatteryState::Discharging => "▾",
+        BatteryState::Full | BatteryState::NotCharging => "=",
+    };
+    let mut div = Div::new(Span::new(icon).foreground(color.clone()));
+    let percent = Span::new(&format!("{}%", battery.percent)).foreground(color);
+    div.add_span(
+        if battery.percent <= 10 && battery.state != BatteryState::Charging {
+            percent.bold().reverse()
+        } else {
+            percent.bold()
+        },
+    );
+    if let Some(remaining) = battery.remaining {
+        let minutes = remaining.as_secs() / 60;
+        div.add_span(Span::new(" "));
+        div.add_span(
+            Span::new(&format!("{}:{:02}", minutes / 60, minutes % 60))
+                .foreground(Color::White)
+                .dimmed(),
+        );
+    }
+    div
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn supply(status: &str, now: u64, full: u64, rate: u64) -> Supply {
+        Supply {
+            status: status.to_owned(),
+            capacity: Some(now * 100 / full),
+            now: Some(now),
+            full: Some(full),
+            rate: Some(rate),
+        }
+    }
+
+    #[test]
+    fn combined_batteries() {
+        let battery = summarize(&[
+            supply("Discharging", 10_000_000, 40_000_000, 10_000_000),
+            supply("Unknown", 30_000_000, 40_000_000, 0),
+        ])
+        .unwrap();
+        assert_eq!(battery.percent, 50);
+        assert_eq!(battery.state, BatteryState::Discharging);
+        assert_eq!(battery.remaining, Some(Duration::from_secs(4 * 3600)));
+    }
+
+    #[test]
+    fn charging_and_capacity_only() {
+        let battery = summarize(&[supply("Charging", 30, 40, 20)]).unwrap();
+        assert_eq!(battery.state, BatteryState::Charging);
+        assert_eq!(battery.remaining, Some(Duration::from_secs(1800)));
+
+        let battery = summarize(&[Supply {
+            status: "Full".to_owned(),
+            capacity: Some(100),
+            ..Default::default()
+        }])
+        .unwrap();
+        assert_eq!(battery.percent, 100);
+        assert_eq!(battery.remaining, None);
+        assert!(!should_show(&battery, 20));
+        assert_eq!(summarize(&[]), None);
+    }
+}
@@ -0,0 +1,249 @@
+/*
+ * Promptly: show a prompt, sooner.
+ * Copyright (C) 2017  Terrence Cole
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+use crate::{
+    keyvalue::{self, Entry},
+    layout::{Color, Div, Span},
+};
+use std::{
+    env::var_os,
+    fs::read_to_string,
+    path::{Path, PathBuf},
+};
+
+const HOST_ARCHES: &[&str] = &[
+    "x86_64",
+    "i686",
+    "aarch64",
+    "armv7",
+    "arm",
+    "riscv64gc",
+    "powerpc64le",
+    "s390x",
+    "loongarch64",
+];
+
+pub struct CargoProject {
+    pub name: String,
+    pub version: Option<String>,
+    pub toolchain: Option<String>,
+}
+
+// Find the package, or failing that the workspace, that owns `cwd`, along with
+// the toolchain rustup would pick for it. Neither rustc nor rustup is run.
+pub fn find_cargo_project(cwd: &Path) -> Option<CargoProject> {
+    let (manifest_dir, manifest) = cwd.ancestors().find_map(|dir| {
+        keyvalue::parse_file(&dir.join("Cargo.toml")).map(|entries| (dir, entries))
+    })?;
+
+    let (name, mut version) = match keyvalue::get(&manifest, "package", "name") {
+        Some(name) => (
+            name.to_owned(),
+            keyvalue::get(&manifest, "package", "version").map(|v| v.to_owned()),
+        ),
+        None => (
+            dir_name(manifest_dir),
+            keyvalue::get(&manifest, "workspace.package", "version").map(|v| v.to_owned()),
+        ),
+    };
+    if keyvalue::get(&manifest, "package", "version.workspace") == Some("true") {
+        version = find_workspace_version(manifest_dir);
+    }
+
+    Some(CargoProject {
+        name,
+        version,
+        toolchain: find_toolchain(cwd),
+    })
+}
+
+// Members that inherit their version get it from the nearest enclosing manifest
+// with a [workspace] table.
+fn find_workspace_version(member_dir: &Path) -> Option<String> {
+    member_dir.ancestors().skip(1).find_map(|dir| {
+        let entries = keyvalue::parse_file(&dir.join("Cargo.toml"))?;
+        if !entries.iter().any(|e| e.section.starts_with("workspace")) {
+            return None;
+        }
+        keyvalue::get(&entries, "workspace.package", "version").map(|v| v.to_owned())
+    })
+}
+
+fn dir_name(dir: &Path) -> String {
+    dir.file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| "/".to_owned())
+}
+
+fn rustup_home() -> Option<PathBuf> {
+    match var_os("RUSTUP_HOME") {
+        Some(home) => Some(PathBuf::from(home)),
+        None => var_os("HOME").map(|home| PathBuf::from(home).join(".rustup")),
+    }
+}
+
+// Follow rustup's precedence: the RUSTUP_TOOLCHAIN environment variable, then a
+// directory override, then a rust-toolchain file, then the default toolchain.
+fn find_toolchain(cwd: &Path) -> Option<String> {
+    if let Some(toolchain) = var_os("RUSTUP_TOOLCHAIN") {
+        return Some(short_toolchain(&toolchain.to_string_lossy()));
+    }
+
+    let settings = rustup_home()
+        .and_then(|home| keyvalue::parse_file(&home.join("settings.toml")))
+        .unwrap_or_default();
+    for dir in cwd.ancestors() {
+        if let Some(toolchain) = find_directory_override(&settings, dir) {
+            return Some(short_toolchain(toolchain));
+        }
+        if let Some(toolchain) = read_toolchain_file(dir) {
+            return Some(short_toolchain(&toolchain));
+        }
+    }
+    keyvalue::get(&settings, "", "default_toolchain").map(short_toolchain)
+}
+
+fn find_directory_override<'a>(settings: &'a [Entry], dir: &Path) -> Option<&'a str> {
+    settings
+        .iter()
+        .find(|e| e.section == "overrides" && Path::new(&e.key) == dir)
+        .map(|e| e.value.as_str())
+}
+
+// Either rust-toolchain.toml, or the legacy rust-toolchain, which may hold TOML
+// or just the bare name of a channel.
+fn read_toolchain_file(dir: &Path) -> Option<String> {
+    for file_name in ["rust-toolchain.toml", "rust-toolchain"] {
+        let path = dir.join(file_name);
+        let Ok(content) = read_to_string(&path) else {
+            continue;
+        };
+        let entries = keyvalue::parse(&content);
+        if let Some(channel) = keyvalue::get(&entries, "toolchain", "channel")
+            .or_else(|| keyvalue::get(&entries, "toolchain", "path"))
+        {
+            return Some(channel.to_owned());
+        }
+        let bare = content.trim();
+        if !bare.is_empty() && !bare.contains('\n') && !bare.contains('=') {
+            return Some(bare.to_owned());
+        }
+    }
+    None
+}
+
+// Drop the host triple from e.g. stable-x86_64-unknown-linux-gnu; everyone
+// knows which host they are on.
+fn short_toolchain(toolchain: &str) -> String {
+    for arch in HOST_ARCHES {
+        if let Some(at) = toolchain.find(&format!("-{}-", arch)) {
+            return toolchain[..at].to_owned();
+        }
+    }
+    toolchain.to_owned()
+}
+
+pub fn format_cargo_project(project: &CargoProject) -> Div {
+    let mut div = Div::new_empty();
+    div.add_span(Span::new("@").foreground(Color::Yellow));
+    div.add_span(Span::new("rs").foreground(Color::Cyan));
+    div.add_span(Span::new("{").bold());
+    div.add_span(Span::new(&project.name).foreground(Color::Yellow).bold());
+    if let Some(version) = &project.version {
+        div.add_span(Span::new(" "));
+        div.add_span(Span::new(version).foreground(Color::Green).dimmed());
+    }
+    if let Some(toolchain) = &project.toolchain {
+        div.add_span(Span::new(" "));
+        div.add_span(Span::new(toolchain).foreground(Color::Purple).dimmed());
+    }
+    div.add_span(Span::new("}").bold());
+    div
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        layout::{Layout, LayoutOptions},
+        render::Run,
+        scratch::ScratchDir,
+    };
+    use std::fs::{create_dir_all, write};
+
+    #[test]
+    fn inherited_version() {
+        let root = ScratchDir::new("cargo-workspace");
+        let member = root.join("member");
+        create_dir_all(&member).unwrap();
+        write(
+            root.join("Cargo.toml"),
+            "[workspace]\nmembers = [\n    \"member\",\n]\n[workspace.package]\nversion = \"1.2.3\"\n",
+        )
+        .unwrap();
+        write(
+            member.join("Cargo.toml"),
+            "[package]\nname = \"member\"\nversion = { workspace = true }\n",
+        )
+        .unwrap();
+        let project = find_cargo_project(&member).unwrap();
+        assert_eq!(project.name, "member");
+        assert_eq!(project.version.as_deref(), Some("1.2.3"));
+    }
+
+    #[test]
+    fn hostile_manifest() {
+        let dir = ScratchDir::new("cargo-hostile");
+        write(
+            dir.join("Cargo.toml"),
+            "[package]\nname = \"$(id)\"\nversion = \"`id`\"\n",
+        )
+        .unwrap();
+        write(dir.join("rust-toolchain"), "$(touch${IFS}/tmp/pwned)\n").unwrap();
+        let project = find_cargo_project(&dir).map(|p| CargoProject {
+            toolchain: read_toolchain_file(&dir),
+            ..p
+        });
+
+        let options = LayoutOptions::new()
+            .width(80)
+            .use_color(false)
+            .use_safe_corners(true)
+            .escape_for_readline(true);
+        let div = format_cargo_project(&project.unwrap());
+        let layout = Layout::build(Div::new(Span::new("T")), vec![div], vec![], &options).unwrap();
+        let out = Run::render_layout(&layout)
+            .iter()
+            .map(|r| r.format(true))
+            .collect::<String>();
+        assert!(
+            out.contains(r"{\\$(id) \\`id\\` \\$(touch\\${IFS}/tmp/pwned)}"),
+            "{}",
+            out
+        );
+    }
+
+    #[test]
+    fn short_toolchains() {
+        assert_eq!(short_toolchain("stable-x86_64-unknown-linux-gnu"), "stable");
+        assert_eq!(
+            short_toolchain("nightly-2024-01-01-aarch64-apple-darwin"),
+            "nightly-2024-01-01"
+        );
+        assert_eq!(short_toolchain("1.93.1"), "1.93.1");
+    }
+}
@@ -0,0 +1,395 @@
+/*
+ * Promptly: show a prompt, sooner.
+ * Copyright (C) 2017  Terrence Cole
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+use crate::{
+    filesystem::{create_runtime_dir, mtime_key, read_private_file, write_private_file},
+    json,
+    keyvalue::{self, Entry},
+    layout::{Color, Div, Span},
+};
+use chrono::{DateTime, Utc};
+use std::{
+    env::{var, var_os},
+    fs::{read_dir, read_to_string},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+pub struct CloudContext {
+    pub provider: &'static str,
+    pub name: String,
+    pub detail: Option<String>,
+    pub expires: Option<DateTime<Utc>>,
+}
+
+// Everything here comes from environment variables and the files the cloud
+// CLIs leave behind; none of them is run, and nothing goes over the network.
+pub fn find_cloud_contexts() -> Vec<CloudContext> {
+    [find_aws_context(), find_gcp_context(), find_azure_context()]
+        .into_iter()
+        .flatten()
+        .collect()
+}
+
+fn home_path(env_override: &str, default: &[&str]) -> Option<PathBuf> {
+    if let Some(path) = var_os(env_override) {
+        return Some(PathBuf::from(path));
+    }
+    let mut path = PathBuf::from(var_os("HOME")?);
+    path.extend(default);
+    Some(path)
+}
+
+// Credential caches write RFC 3339, except for older SSO caches, which end in
+// "UTC" rather than "Z".
+fn parse_expiry(s: &str) -> Option<DateTime<Utc>> {
+    let s = s.trim();
+    let s = match s.strip_suffix("UTC") {
+        Some(bare) => format!("{}Z", bare),
+        None => s.to_owned(),
+    };
+    DateTime::parse_from_rfc3339(&s)
+        .ok()
+        .map(|t| t.with_timezone(&Utc))
+}
+
+// Without a profile in the environment, the CLI uses [default], if there is one.
+fn aws_profile(from_env: Option<String>, config: &[Entry]) -> Option<String> {
+    from_env.or_else(|| {
+        config
+            .iter()
+            .any(|e| e.section == "default")
+            .then(|| "default".to_owned())
+    })
+}
+
+fn find_aws_context() -> Option<CloudContext> {
+    let config = home_path("AWS_CONFIG_FILE", &[".aws", "config"])
+        .and_then(|path| keyvalue::parse_file(&path))
+        .unwrap_or_default();
+    let profile = aws_profile(
+        ["AWS_PROFILE", "AWS_DEFAULT_PROFILE", "AWS_VAULT"]
+            .iter()
+            .find_map(|name| var(name).ok().filter(|v| !v.is_empty())),
+        &config,
+    )?;
+    let section = if profile == "default" {
+        "default".to_owned()
+    } else {
+        format!("profile {}", profile)
+    };
+
+    let region = var("AWS_REGION")
+        .or_else(|_| var("AWS_DEFAULT_REGION"))
+        .ok()
+        .or_else(|| keyvalue::get(&config, &section, "region").map(|r| r.to_owned()));
+
+    // aws-vault and granted export the expiry of the credentials they hand out.
+    let expires = ["AWS_CREDENTIAL_EXPIRATION", "AWS_SESSION_EXPIRATION"]
+        .iter()
+        .find_map(|name| var(name).ok().and_then(|v| parse_expiry(&v)))
+        .or_else(|| find_aws_cached_expiry(&config, &section, &profile));
+
+    Some(CloudContext {
+        provider: "aws",
+        name: profile,
+        detail: region,
+        expires,
+    })
+}
+
+fn find_aws_cached_expiry(config: &[Entry], section: &str, profile: &str) -> Option<DateTime<Utc>> {
+    // Tools like saml2aws write the expiry next to the keys themselves.
+    let credentials = home_path("AWS_SHARED_CREDENTIALS_FILE", &[".aws", "credentials"])
+        .and_then(|path| keyvalue::parse_file(&path))
+        .unwrap_or_default();
+    if let Some(expires) = ["x_security_token_expires", "expiration"]
+        .iter()
+        .find_map(|key| keyvalue::get(&credentials, profile, key))
+        .and_then(parse_expiry)
+    {
+        return Some(expires);
+    }
+
+    let aws_dir = PathBuf::from(var_os("HOME")?).join(".aws");
+
+    // SSO logins are cached per start url, which is either on the profile or on
+    // the sso-session it names.
+    let start_url = keyvalue::get(config, section, "sso_start_url").or_else(|| {
+        let session = keyvalue::get(config, section, "sso_session")?;
+        keyvalue::get(config, &format!("sso-session {}", session), "sso_start_url")
+    });
+    let cache_dir = create_runtime_dir().ok();
+    if let Some(start_url) = start_url {
+        return latest_cached_expiry(
+            &aws_dir.join("sso").join("cache"),
+            cache_dir.map(|d| d.join("aws-sso-expiry")).as_deref(),
+            start_url,
+            |pairs| {
+                (json::get(pairs, "startUrl") == Some(start_url))
+                    .then(|| json::get(pairs, "expiresAt"))
+                    .flatten()
+            },
+        );
+    }
+
+    // The CLI names its assumed-role cache files by a hash of the request, so
+    // match on the role in the cached arn instead:
+    //   arn:aws:iam::123456789012:role/Admin
+    //   arn:aws:sts::123456789012:assumed-role/Admin/session
+    let role_arn = keyvalue::get(config, section, "role_arn")?;
+    let (account, role) = parse_role_arn(role_arn)?;
+    let prefix = format!(":{}:assumed-role/{}/", account, role);
+    latest_cached_expiry(
+        &aws_dir.join("cli").join("cache"),
+        cache_dir.map(|d| d.join("aws-role-expiry")).as_deref(),
+        &prefix,
+        |pairs| {
+            json::get(pairs, "AssumedRoleUser.Arn")?
+                .contains(&prefix)
+                .then(|| json::get(pairs, "Credentials.Expiration"))
+                .flatten()
+        },
+    )
+}
+
+fn parse_role_arn(arn: &str) -> Option<(&str, &str)> {
+    let mut parts = arn.splitn(6, ':');
+    let account = parts.nth(4)?;
+    let role = parts.next()?.strip_prefix("role/")?;
+    Some((account, role.rsplit('/').next()?))
+}
+
+// Reading every cached credential on each prompt adds up, so keep the answer in
+// `cache` until a file in `dir` changes, as kube.rs does. What we looked for is
+// part of the key, so switching profiles does not reuse another's answer.
+fn latest_cached_expiry<F>(
+    dir: &Path,
+    cache: Option<&Path>,
+    looking_for: &str,
+    expiry_of: F,
+) -> Option<DateTime<Utc>>
+where
+    F: Fn(&[(String, String)]) -> Option<&str>,
+{
+    let mut paths = read_dir(dir)
+        .ok()?
+        .flatten()
+        .map(|entry| entry.path())
+        .collect::<Vec<PathBuf>>();
+    paths.sort();
+    let key = format!("{} {}", looking_for, mtime_key(&paths));
+    if let Some(cached) = cache.and_then(|p| read_private_file(p).ok()) {
+        let mut lines = cached.lines();
+        if lines.next() == Some(key.as_str()) {
+            return lines.next().and_then(parse_expiry);
+        }
+    }
+
+    let expires = paths
+        .iter()
+        .filter_map(|path| json::flatten_file(path))
+        .filter_map(|pairs| expiry_of(&pairs).and_then(parse_expiry))
+        .max();
+    if let Some(cache) = cache {
+        let value = expires.map(|e| e.to_rfc3339()).unwrap_or_default();
+        write_private_file(cache, &format!("{}\n{}\n", key, value)).ok();
+    }
+    expires
+}
+
+fn find_gcp_context() -> Option<CloudContext> {
+    let config_dir = home_path("CLOUDSDK_CONFIG", &[".config", "gcloud"])?;
+    let name = match var("CLOUDSDK_ACTIVE_CONFIG_NAME") {
+        Ok(name) => name,
+        Err(_) => read_to_string(config_dir.join("active_config"))
+            .ok()?
+            .trim()
+            .to_owned(),
+    };
+    if name.is_empty() {
+        return None;
+    }
+    let config = keyvalue::parse_file(
+        &config_dir
+            .join("configurations")
+            .join(format!("config_{}", name)),
+    )
+    .unwrap_or_default();
+    let project = var("CLOUDSDK_CORE_PROJECT")
+        .ok()
+        .or_else(|| keyvalue::get(&config, "core", "project").map(|p| p.to_owned()));
+    Some(CloudContext {
+        provider: "gcp",
+        name,
+        detail: project,
+        expires: None,
+    })
+}
+
+fn find_azure_context() -> Option<CloudContext> {
+    let profile = home_path("AZURE_CONFIG_DIR", &[".azure"])?.join("azureProfile.json");
+    let pairs = json::flatten_file(&profile)?;
+    let default_at = pairs
+        .iter()
+        .find(|(path, value)| {
+            path.starts_with("subscriptions.") && path.ends_with(".isDefault") && value == "true"
+        })
+        .map(|(path, _)| path.trim_end_matches(".isDefault"))?;
+    let name = json::get(&pairs, &format!("{}.name", default_at))?;
+    Some(CloudContext {
+        provider: "az",
+        name: name.to_owned(),
+        detail: None,
+        expires: None,
+    })
+}
+
+fn format_remaining(seconds: u64) -> String {
+    match seconds {
+        s if s >= 3600 => format!("{}h{}m", s / 3600, s % 3600 / 60),
+        s if s >= 60 => format!("{}m", s / 60),
+        s => format!("{}s", s),
+    }
+}
+
+pub fn format_cloud_context(
+    context: &CloudContext,
+    now: DateTime<Utc>,
+    warn_within: Duration,
+) -> Div {
+    let mut div = Div::new_empty();
+    div.add_span(Span::new("@").foreground(Color::Yellow));
+    div.add_span(Span::new(context.provider).foreground(Color::Cyan));
+    div.add_span(Span::new("{").bold());
+    div.add_span(Span::new(&context.name).foreground(Color::Yellow).bold());
+    if let Some(detail) = &context.detail {
+        div.add_span(Span::new(" "));
+        div.add_span(Span::new(detail).foreground(Color::Green).dimmed());
+    }
+    if let Some(expires) = context.expires {
+        let remaining = (expires - now).num_seconds();
+        if remaining <= 0 {
+            div.add_span(Span::new(" "));
+            div.add_span(Span::new("expired").foreground(Color::Red).bold());
+        } else if (remaining as u64) < warn_within.as_secs() {
+            div.add_span(Span::new(" ⧗").foreground(Color::Yellow));
+            div.add_span(
+                Span::new(&format_remaining(remaining as u64))
+                    .foreground(Color::Yellow)
+                    .bold(),
+            );
+        }
+    }
+    div.add_span(Span::new("}").bold());
+    div
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scratch::ScratchDir;
+    use std::fs::{create_dir, write};
+
+    #[test]
+    fn expiry_formats() {
+        let z = parse_expiry("2024-05-01T12:00:00Z").unwrap();
+        assert_eq!(parse_expiry("2024-05-01T12:00:00UTC"), Some(z));
+        assert_eq!(parse_expiry("2024-05-01T14:00:00+02:00"), Some(z));
+        assert_eq!(parse_expiry("tomorrow"), None);
+    }
+
+    #[test]
+    fn role_arns() {
+        assert_eq!(
+            parse_role_arn("arn:aws:iam::123456789012:role/Admin"),
+            Some(("123456789012", "Admin"))
+        );
+        assert_eq!(
+            parse_role_arn("arn:aws:iam::123456789012:role/team/Deploy"),
+            Some(("123456789012", "Deploy"))
+        );
+        assert_eq!(parse_role_arn("arn:aws:iam::123456789012:user/me"), None);
+    }
+
+    #[test]
+    fn default_profile() {
+        let config = keyvalue::parse("[default]\nregion = us-east-1\n[profile prod]\n");
+        assert_eq!(
+            aws_profile(Some("prod".to_owned()), &config).as_deref(),
+            Some("prod")
+        );
+        assert_eq!(aws_profile(None, &config).as_deref(), Some("default"));
+        let config = keyvalue::parse("[profile prod]\nregion = eu-west-1\n");
+        assert_eq!(aws_profile(None, &config), None);
+    }
+
+    #[test]
+    fn cached_expiry() {
+        let root = ScratchDir::new("aws-cache");
+        let sso = root.join("sso");
+        create_dir(&sso).unwrap();
+        let cache = root.join("expiry");
+        let token = |url: &str, expires: &str| {
+            format!(r#"{{"startUrl": "{}", "expiresAt": "{}"}}"#, url, expires)
+        };
+        let url = "https://example.awsapps.com/start";
+        write(sso.join("a.json"), token(url, "2024-05-01T12:00:00Z")).unwrap();
+        write(
+            sso.join("b.json"),
+            token("https://other", "2024-06-01T12:00:00Z"),
+        )
+        .unwrap();
+        let expiry = || {
+            latest_cached_expiry(&sso, Some(&cache), url, |pairs| {
+                (json::get(pairs, "startUrl") == Some(url))
+                    .then(|| json::get(pairs, "expiresAt"))
+                    .flatten()
+            })
+        };
+        assert_eq!(expiry(), parse_expiry("2024-05-01T12:00:00Z"));
+
+        // While no file changes, the cached answer stands.
+        let cached = read_to_string(&cache).unwrap();
+        let key = cached.lines().next().unwrap();
+        write(&cache, format!("{}\n2030-01-01T00:00:00Z\n", key)).unwrap();
+        assert_eq!(expiry(), parse_expiry("2030-01-01T00:00:00Z"));
+
+        // A new login writes a new file.
+        write(sso.join("c.json"), token(url, "2024-05-02T12:00:00Z")).unwrap();
+        assert_eq!(expiry(), parse_expiry("2024-05-02T12:00:00Z"));
+    }
+
+    #[test]
+    fn expiry_warnings() {
+        let now = parse_expiry("2024-05-01T12:00:00Z").unwrap();
+        let context = |expires: &str| CloudContext {
+            provider: "aws",
+            name: "prod".to_owned(),
+            detail: Some("Admin".to_owned()),
+            expires: parse_expiry(expires),
+        };
+        let text = |expires: &str| {
+            format_cloud_context(&context(expires), now, Duration::from_secs(900)).text()
+        };
+        assert_eq!(text("2024-05-01T11:59:00Z"), "@aws{prod Admin expired}");
+        assert_eq!(text("2024-05-01T12:00:00Z"), "@aws{prod Admin expired}");
+        assert_eq!(text("2024-05-01T12:10:30Z"), "@aws{prod Admin ⧗10m}");
+        assert_eq!(text("2024-05-01T12:00:45Z"), "@aws{prod Admin ⧗45s}");
+        assert_eq!(text("2024-05-01T13:00:00Z"), "@aws{prod Admin}");
+    }
+}
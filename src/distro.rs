@@ -0,0 +1,184 @@
+/*
+ * Promptly: show a prompt, sooner.
+ * Copyright (C) 2017  Terrence Cole
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+use crate::{
+    host, keyvalue,
+    layout::{Color, Div, Span},
+};
+use std::{path::Path, str::FromStr};
+
+const OS_RELEASE_PATHS: &[&str] = &["/etc/os-release", "/usr/lib/os-release"];
+
+// Colors people will recognize from each distribution's logo.
+const DISTRO_COLORS: &[(&str, Color)] = &[
+    ("debian", Color::Red),
+    ("raspbian", Color::Red),
+    ("ubuntu", Color::Yellow),
+    ("fedora", Color::Blue),
+    ("rhel", Color::Red),
+    ("centos", Color::Purple),
+    ("alpine", Color::Blue),
+    ("nixos", Color::Cyan),
+    ("arch", Color::Cyan),
+    ("opensuse", Color::Green),
+    ("suse", Color::Green),
+    ("gentoo", Color::Purple),
+];
+
+// Some IDs are long, or are not what anyone calls the distribution.
+const SHORT_LABELS: &[(&str, &str)] = &[
+    ("opensuse-tumbleweed", "suse"),
+    ("opensuse-leap", "suse"),
+    ("raspbian", "pi"),
+    ("archarm", "arch"),
+    ("linuxmint", "mint"),
+];
+
+#[derive(Debug, PartialEq)]
+pub struct Distro {
+    pub id: String,
+    // The distributions this one derives from, nearest first.
+    pub id_like: Vec<String>,
+    pub label: String,
+    pub version: Option<String>,
+    pub arch: Option<String>,
+}
+
+// Color a distribution by its os-release ID, e.g. `nixos=purple`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DistroColor {
+    pub id: String,
+    pub color: Color,
+}
+
+impl FromStr for DistroColor {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (id, color) = s
+            .split_once('=')
+            .ok_or_else(|| format!("expected ID=COLOR, not '{}'", s))?;
+        Ok(DistroColor {
+            id: id.to_owned(),
+            color: color.parse()?,
+        })
+    }
+}
+
+pub fn find_distro() -> Option<Distro> {
+    let entries = OS_RELEASE_PATHS
+        .iter()
+        .find_map(|path| keyvalue::parse_file(Path::new(path)))?;
+    let mut distro = parse_os_release(&entries)?;
+    distro.arch = host::machine_arch();
+    Some(distro)
+}
+
+// os-release is a list of shell assignments, which our key=value reader handles.
+fn parse_os_release(entries: &[keyvalue::Entry]) -> Option<Distro> {
+    let get = |key: &str| keyvalue::get(entries, "", key).filter(|v| !v.is_empty());
+    let id = get("ID").unwrap_or("linux").to_owned();
+    let label = match SHORT_LABELS.iter().find(|(long, _)| *long == id) {
+        Some((_, short)) => (*short).to_owned(),
+        // Without an ID, the first word of the pretty name is usually the name.
+        None if get("ID").is_none() => get("PRETTY_NAME")
+            .or_else(|| get("NAME"))
+            .and_then(|name| name.split_whitespace().next())?
+            .to_lowercase(),
+        None => id.clone(),
+    };
+    Some(Distro {
+        id,
+        id_like: get("ID_LIKE")
+            .map(|like| like.split_whitespace().map(|s| s.to_owned()).collect())
+            .unwrap_or_default(),
+        label,
+        // Rolling releases like Arch have no version at all.
+        version: get("VERSION_ID").map(|v| v.to_owned()),
+        arch: None,
+    })
+}
+
+// The user's choice wins, then a color for the distribution itself, then one
+// for whatever it is derived from.
+fn distro_color(distro: &Distro, overrides: &[DistroColor]) -> Color {
+    let ids = || std::iter::once(&distro.id).chain(distro.id_like.iter());
+    ids()
+        .find_map(|id| {
+            overrides
+                .iter()
+                .find(|o| &o.id == id)
+                .map(|o| o.color.clone())
+        })
+        .or_else(|| {
+            ids().find_map(|id| {
+                DISTRO_COLORS
+                    .iter()
+                    .find(|(name, _)| name == id)
+                    .map(|(_, color)| color.clone())
+            })
+        })
+        .unwrap_or(Color::White)
+}
+
+pub fn format_distro(distro: &Distro, overrides: &[DistroColor]) -> Div {
+    let color = distro_color(distro, overrides);
+    let mut div = Div::new(Span::new(&distro.label).foreground(color.clone()).bold());
+    if let Some(version) = &distro.version {
+        div.add_span(Span::new(" "));
+        div.add_span(Span::new(version).foreground(color));
+    }
+    if let Some(arch) = &distro.arch {
+        div.add_span(Span::new(" "));
+        div.add_span(Span::new(arch).foreground(Color::White).dimmed());
+    }
+    div
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn distro(content: &str) -> Option<Distro> {
+        parse_os_release(&keyvalue::parse(content))
+    }
+
+    #[test]
+    fn os_release() {
+        let debian = distro(
+            "PRETTY_NAME=\"Debian GNU/Linux 12 (bookworm)\"\nID=debian\nVERSION_ID=\"12\"\n",
+        )
+        .unwrap();
+        assert_eq!(debian.label, "debian");
+        assert_eq!(debian.version.as_deref(), Some("12"));
+
+        let arch = distro("NAME=\"Arch Linux\"\nID=arch\nBUILD_ID=rolling\n").unwrap();
+        assert_eq!(arch.version, None);
+
+        let pi = distro("ID=raspbian\nID_LIKE=debian\nVERSION_ID=\"11\"\n").unwrap();
+        assert_eq!(pi.label, "pi");
+        assert_eq!(distro_color(&pi, &[]), Color::Red);
+
+        let pop = distro("ID=pop\nID_LIKE=\"ubuntu debian\"\n").unwrap();
+        assert_eq!(distro_color(&pop, &[]), Color::Yellow);
+        let overrides = ["debian=green".parse::<DistroColor>().unwrap()];
+        assert_eq!(distro_color(&pop, &overrides), Color::Green);
+
+        let bare = distro("PRETTY_NAME=\"Mystery OS 1\"\n").unwrap();
+        assert_eq!(bare.label, "mystery");
+    }
+}
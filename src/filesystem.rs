@@ -0,0 +1,364 @@
+/*
+ * Promptly: show a prompt, sooner.
+ * Copyright (C) 2017  Terrence Cole
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+use std::{
+    env::{temp_dir, var_os},
+    ffi::CString,
+    fs::{DirBuilder, OpenOptions, metadata, read_to_string, symlink_metadata},
+    io::{self, Read, Write},
+    mem::MaybeUninit,
+    os::unix::{
+        ffi::OsStrExt,
+        fs::{DirBuilderExt, MetadataExt, OpenOptionsExt},
+    },
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+use users::get_current_uid;
+
+// Filesystem magic numbers, from statfs(2), for filesystems where every stat
+// may be a network round trip.
+const REMOTE_MAGICS: &[(u32, &str)] = &[
+    (0x0000_6969, "nfs"),
+    (0x0000_517b, "smb"),
+    (0xff53_4d42, "cifs"),
+    (0xfe53_4d42, "smb2"),
+    (0x0102_1997, "9p"),
+    (0x00c3_6400, "ceph"),
+    (0x5346_414f, "afs"),
+    (0x7375_7245, "coda"),
+    (0x0000_564c, "ncp"),
+];
+
+const FUSE_MAGIC: u32 = 0x6573_5546;
+
+// FUSE is used for local filesystems too, so we only count the ones we know to
+// be backed by a network connection or a VM host.
+const REMOTE_FUSE_TYPES: &[&str] = &[
+    "virtiofs",
+    "fuse.sshfs",
+    "fuse.rclone",
+    "fuse.s3fs",
+    "fuse.gcsfuse",
+    "fuse.goofys",
+    "fuse.davfs",
+    "fuse.curlftpfs",
+    "fuse.gvfsd-fuse",
+];
+
+// Where we keep state between prompts: $XDG_RUNTIME_DIR, which is per-user and
+// cleared on logout, or without it, a private directory under the temp dir.
+fn runtime_dir() -> PathBuf {
+    match var_os("XDG_RUNTIME_DIR") {
+        Some(dir) => PathBuf::from(dir).join("promptly"),
+        None => temp_dir().join(format!("promptly-{}", get_current_uid())),
+    }
+}
+
+// The temp dir is shared, so someone else may have made our directory first,
+// or left a symlink there. Only use it if it is ours alone.
+pub fn create_runtime_dir() -> io::Result<PathBuf> {
+    let dir = runtime_dir();
+    DirBuilder::new().recursive(true).mode(0o700).create(&dir)?;
+    check_private_dir(&dir)?;
+    Ok(dir)
+}
+
+fn check_private_dir(dir: &Path) -> io::Result<()> {
+    let meta = symlink_metadata(dir)?;
+    if !meta.is_dir() || meta.uid() != get_current_uid() || meta.mode() & 0o777 != 0o700 {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!("{} is not a private directory", dir.display()),
+        ));
+    }
+    Ok(())
+}
+
+// Files in the runtime dir are never symlinks we made, so refuse to follow one.
+pub fn read_private_file(path: &Path) -> io::Result<String> {
+    let mut content = String::new();
+    OpenOptions::new()
+        .read(true)
+        .custom_flags(libc::O_NOFOLLOW)
+        .open(path)?
+        .read_to_string(&mut content)?;
+    Ok(content)
+}
+
+pub fn write_private_file(path: &Path, content: &str) -> io::Result<()> {
+    OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .custom_flags(libc::O_NOFOLLOW)
+        .open(path)?
+        .write_all(content.as_bytes())
+}
+
+// A cache key that changes whenever any of the files does: each path with its
+// mtime, or 0 for one that does not exist.
+pub fn mtime_key(paths: &[PathBuf]) -> String {
+    paths
+        .iter()
+        .map(|p| {
+            let mtime = metadata(p)
+                .and_then(|m| m.modified())
+                .ok()
+                .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                .map(|d| d.as_nanos())
+                .unwrap_or(0);
+            format!("{}@{}", p.display(), mtime)
+        })
+        .collect::<Vec<String>>()
+        .join(":")
+}
+
+// Both paths lead to the same file, however they got there.
+pub fn is_same_file(a: &Path, b: &Path) -> bool {
+    match (metadata(a), metadata(b)) {
+        (Ok(a), Ok(b)) => a.dev() == b.dev() && a.ino() == b.ino(),
+        _ => false,
+    }
+}
+
+fn to_cstring(path: &Path) -> Option<CString> {
+    CString::new(path.as_os_str().as_bytes()).ok()
+}
+
+// Ask the kernel whether we could write here with our real uid and gid. This takes
+// ACLs and capabilities into account, which mode bits alone would not.
+pub fn is_writable(path: &Path) -> bool {
+    match to_cstring(path) {
+        Some(c_path) => unsafe { libc::access(c_path.as_ptr(), libc::W_OK) == 0 },
+        None => true,
+    }
+}
+
+pub fn statvfs(path: &Path) -> Option<libc::statvfs> {
+    let c_path = to_cstring(path)?;
+    let mut buf = MaybeUninit::<libc::statvfs>::uninit();
+    if unsafe { libc::statvfs(c_path.as_ptr(), buf.as_mut_ptr()) } != 0 {
+        return None;
+    }
+    Some(unsafe { buf.assume_init() })
+}
+
+pub fn is_read_only_mount(path: &Path) -> bool {
+    match statvfs(path) {
+        Some(stat) => stat.f_flag & libc::ST_RDONLY != 0,
+        None => false,
+    }
+}
+
+pub struct DiskUsage {
+    // Bytes an unprivileged user can still write.
+    pub available: u64,
+    pub used_percent: u64,
+}
+
+// Count space reserved for root as neither used nor free, the same as df does.
+pub fn disk_usage(path: &Path) -> Option<DiskUsage> {
+    let stat = statvfs(path)?;
+    let block_size = stat.f_frsize as u64;
+    let used = (stat.f_blocks as u64).saturating_sub(stat.f_bfree as u64);
+    let usable = used + stat.f_bavail as u64;
+    if usable == 0 {
+        return None;
+    }
+    Some(DiskUsage {
+        available: stat.f_bavail as u64 * block_size,
+        used_percent: (used * 100).div_ceil(usable),
+    })
+}
+
+const SIZE_UNITS: &[(&str, u64)] = &[
+    ("T", 1 << 40),
+    ("G", 1 << 30),
+    ("M", 1 << 20),
+    ("K", 1 << 10),
+];
+
+// Sizes like "512M" or "1.5G", in powers of 1024; a bare number is bytes.
+pub fn parse_size(s: &str) -> Result<u64, String> {
+    let s = s.trim();
+    let upper = s.to_uppercase();
+    let number = upper.trim_end_matches(['B', 'I']);
+    let (number, scale) = SIZE_UNITS
+        .iter()
+        .find_map(|(unit, scale)| Some((number.strip_suffix(unit)?, *scale)))
+        .unwrap_or((number, 1));
+    let value = number
+        .trim()
+        .parse::<f64>()
+        .map_err(|_| format!("invalid size '{}'", s))?;
+    if value < 0.0 {
+        return Err(format!("invalid size '{}'", s));
+    }
+    Ok((value * scale as f64) as u64)
+}
+
+pub fn format_size(bytes: u64) -> String {
+    for (unit, scale) in SIZE_UNITS {
+        if bytes >= *scale {
+            let value = bytes as f64 / *scale as f64;
+            return if value < 10.0 {
+                format!("{:.1}{}", value, unit)
+            } else {
+                format!("{:.0}{}", value, unit)
+            };
+        }
+    }
+    format!("{}B", bytes)
+}
+
+pub fn statfs(path: &Path) -> Option<libc::statfs> {
+    let c_path = to_cstring(path)?;
+    let mut buf = MaybeUninit::<libc::statfs>::uninit();
+    if unsafe { libc::statfs(c_path.as_ptr(), buf.as_mut_ptr()) } != 0 {
+        return None;
+    }
+    Some(unsafe { buf.assume_init() })
+}
+
+// Return a short name for the filesystem holding `path` if it is remote.
+pub fn find_remote_fs(path: &Path) -> Option<String> {
+    let magic = statfs(path)?.f_type as u32;
+    if let Some((_, name)) = REMOTE_MAGICS.iter().find(|(m, _)| *m == magic) {
+        return Some(name.to_string());
+    }
+    if magic == FUSE_MAGIC {
+        let mountinfo = read_to_string("/proc/self/mountinfo").ok()?;
+        let fs_type = find_mount_fs_type(&mountinfo, path)?;
+        if REMOTE_FUSE_TYPES.contains(&fs_type.as_str()) {
+            return Some(fs_type.trim_start_matches("fuse.").to_owned());
+        }
+    }
+    None
+}
+
+// Find the type of the mount containing `path` by picking the longest matching
+// mount point in /proc/self/mountinfo. Each line looks like:
+//   36 35 98:0 /mnt1 /mnt/parent rw,noatime master:1 - ext3 /dev/root rw
+// Later lines are later mounts, so of two on the same point, the last is on top.
+fn find_mount_fs_type(mountinfo: &str, path: &Path) -> Option<String> {
+    let mut best: Option<(PathBuf, String)> = None;
+    for line in mountinfo.lines() {
+        let mut fields = line.split(' ');
+        let mount_point = match fields.nth(4) {
+            Some(mp) => PathBuf::from(unescape_mount_field(mp)),
+            None => continue,
+        };
+        let fs_type = match fields.skip_while(|f| *f != "-").nth(1) {
+            Some(t) => t,
+            None => continue,
+        };
+        if !path.starts_with(&mount_point) {
+            continue;
+        }
+        let is_longer = best
+            .as_ref()
+            .is_none_or(|(mp, _)| mount_point.as_os_str().len() >= mp.as_os_str().len());
+        if is_longer {
+            best = Some((mount_point, fs_type.to_owned()));
+        }
+    }
+    best.map(|(_, fs_type)| fs_type)
+}
+
+// Spaces, tabs, newlines and backslashes are written as octal escapes.
+fn unescape_mount_field(field: &str) -> String {
+    let mut out = String::new();
+    let mut chars = field.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            let code = chars.by_ref().take(3).collect::<String>();
+            match u8::from_str_radix(&code, 8) {
+                Ok(b) => out.push(b as char),
+                Err(_) => {
+                    out.push(c);
+                    out.push_str(&code);
+                }
+            }
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sizes() {
+        assert_eq!(parse_size("512"), Ok(512));
+        assert_eq!(parse_size("1k"), Ok(1024));
+        assert_eq!(parse_size("1.5G"), Ok(3 << 29));
+        assert_eq!(parse_size("2GiB"), Ok(2 << 30));
+        assert!(parse_size("lots").is_err());
+        assert_eq!(format_size(900), "900B");
+        assert_eq!(format_size(3 << 29), "1.5G");
+        assert_eq!(format_size(200 << 20), "200M");
+    }
+
+    #[test]
+    fn mount_types() {
+        let mountinfo = "\
+22 1 8:1 / / rw,relatime shared:1 - ext4 /dev/sda1 rw
+40 22 0:35 / /home/me/remote rw,nosuid - fuse.sshfs me@host:/ rw
+41 22 0:36 / /home/me/remote rw,nosuid - fuse.rclone drive: rw
+42 22 0:37 / /mnt/My\\040Drive rw - virtiofs share rw
+43 22 0:38 / /home rw,relatime - xfs /dev/sdb1 rw
+";
+        let fs_type = |path: &str| find_mount_fs_type(mountinfo, Path::new(path));
+        assert_eq!(fs_type("/etc").as_deref(), Some("ext4"));
+        assert_eq!(
+            fs_type("/home/me/remote/docs").as_deref(),
+            Some("fuse.rclone")
+        );
+        assert_eq!(fs_type("/home/me/remotes").as_deref(), Some("xfs"));
+        assert_eq!(fs_type("/mnt/My Drive/x").as_deref(), Some("virtiofs"));
+        assert_eq!(unescape_mount_field("a\\134b\\011c"), "a\\b\tc");
+    }
+
+    #[test]
+    fn private_dirs() {
+        use crate::scratch::ScratchDir;
+        use std::{
+            fs::{Permissions, create_dir, set_permissions},
+            os::unix::fs::{PermissionsExt, symlink},
+        };
+        let root = ScratchDir::new("private");
+        let dir = root.join("dir");
+        create_dir(&dir).unwrap();
+        set_permissions(&dir, Permissions::from_mode(0o755)).unwrap();
+        assert!(check_private_dir(&dir).is_err());
+        set_permissions(&dir, Permissions::from_mode(0o700)).unwrap();
+        assert!(check_private_dir(&dir).is_ok());
+        symlink(&dir, root.join("link")).unwrap();
+        assert!(check_private_dir(&root.join("link")).is_err());
+
+        symlink(root.join("elsewhere"), dir.join("file")).unwrap();
+        assert!(write_private_file(&dir.join("file"), "x").is_err());
+        assert!(!root.join("elsewhere").exists());
+        write_private_file(&dir.join("real"), "x").unwrap();
+        assert_eq!(read_private_file(&dir.join("real")).unwrap(), "x");
+    }
+}
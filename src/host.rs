@@ -0,0 +1,204 @@
+/*
+ * Promptly: show a prompt, sooner.
+ * Copyright (C) 2017  Terrence Cole
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+use crate::layout::{Color, Div, Span};
+use std::{
+    ffi::CStr,
+    fs::read_to_string,
+    io,
+    mem::MaybeUninit,
+    path::{Path, PathBuf},
+};
+
+// Written by the postinst scripts of Debian and Ubuntu packages, via
+// update-notifier, when a new kernel or library needs a reboot to take effect.
+// The .pkgs file lists the packages. needrestart keeps no such file and would
+// have to be run to ask, which is too slow for a prompt, so it is not covered.
+const REBOOT_REQUIRED: &str = "/run/reboot-required";
+
+// Where kernel modules are installed. Package managers that replace the running
+// kernel remove its modules, which is as good a sign as any that a reboot is due.
+const MODULES_DIRS: &[&str] = &["/lib/modules", "/usr/lib/modules"];
+
+#[derive(Debug, PartialEq)]
+pub enum RebootReason {
+    // The packages that asked for it, if they said.
+    Packages(usize),
+    Kernel,
+}
+
+// The kernel check means nothing in a container or chroot, whose modules, if
+// any, come from its image rather than from the host's kernel.
+pub fn find_reboot_required(check_kernel: bool) -> Option<RebootReason> {
+    if let Some(reason) = read_reboot_required(Path::new(REBOOT_REQUIRED)) {
+        return Some(reason);
+    }
+    if !check_kernel {
+        return None;
+    }
+    let release = running_kernel_release()?;
+    let installed = MODULES_DIRS
+        .iter()
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_dir())
+        .collect::<Vec<PathBuf>>();
+    // Some minimal installs have no modules at all.
+    if installed.is_empty() || installed.iter().any(|dir| dir.join(&release).exists()) {
+        return None;
+    }
+    Some(RebootReason::Kernel)
+}
+
+// Packages are appended each time one asks, so the same one can appear twice.
+fn read_reboot_required(path: &Path) -> Option<RebootReason> {
+    if !path.exists() {
+        return None;
+    }
+    let mut pkgs_path = path.as_os_str().to_owned();
+    pkgs_path.push(".pkgs");
+    let mut packages = read_to_string(pkgs_path)
+        .unwrap_or_default()
+        .lines()
+        .map(|l| l.trim().to_owned())
+        .filter(|l| !l.is_empty())
+        .collect::<Vec<String>>();
+    packages.sort();
+    packages.dedup();
+    Some(RebootReason::Packages(packages.len()))
+}
+
+fn uname() -> Option<libc::utsname> {
+    let mut name = MaybeUninit::<libc::utsname>::uninit();
+    if unsafe { libc::uname(name.as_mut_ptr()) } != 0 {
+        return None;
+    }
+    Some(unsafe { name.assume_init() })
+}
+
+fn running_kernel_release() -> Option<String> {
+    let name = uname()?;
+    let release = unsafe { CStr::from_ptr(name.release.as_ptr()) };
+    Some(release.to_string_lossy().to_string())
+}
+
+// What the kernel is running, which is not always what we were built for, e.g.
+// a 32 bit userland on a 64 bit Raspberry Pi.
+pub fn machine_arch() -> Option<String> {
+    let name = uname()?;
+    let machine = unsafe { CStr::from_ptr(name.machine.as_ptr()) };
+    Some(machine.to_string_lossy().to_string())
+}
+
+// utmp can hold sessions whose process died without logging out, so check that
+// each one is still there. EPERM just means it belongs to someone else.
+fn is_alive(pid: libc::pid_t) -> bool {
+    pid > 0
+        && (unsafe { libc::kill(pid, 0) } == 0
+            || io::Error::last_os_error().raw_os_error() == Some(libc::EPERM))
+}
+
+// Everyone other than `me` with a live login session, each named once.
+pub fn find_other_users(me: &str) -> Vec<String> {
+    let mut users = Vec::new();
+    unsafe { libc::setutxent() };
+    while let Some(entry) = unsafe { libc::getutxent().as_ref() } {
+        if entry.ut_type != libc::USER_PROCESS || !is_alive(entry.ut_pid) {
+            continue;
+        }
+        let user = entry
+            .ut_user
+            .iter()
+            .take_while(|&&c| c != 0)
+            .map(|&c| c as u8 as char)
+            .collect::<String>();
+        if !user.is_empty() && user != me && !users.contains(&user) {
+            users.push(user);
+        }
+    }
+    unsafe { libc::endutxent() };
+    users
+}
+
+pub fn format_reboot_required(reason: &RebootReason) -> Div {
+    let mut div = Div::new(Span::new("↻").foreground(Color::Red).bold());
+    div.add_span(Span::new("reboot").foreground(Color::Red));
+    match reason {
+        RebootReason::Packages(0) => {}
+        RebootReason::Packages(count) => {
+            div.add_span(
+                Span::new(&format!(" {}", count))
+                    .foreground(Color::Red)
+                    .dimmed(),
+            );
+        }
+        RebootReason::Kernel => {
+            div.add_span(Span::new(" kernel").foreground(Color::Red).dimmed());
+        }
+    }
+    div
+}
+
+// A name or two is more useful than a number; past that, the number will do.
+pub fn format_other_users(users: &[String]) -> Div {
+    let mut div = Div::new(Span::new("⚇").foreground(Color::Yellow).bold());
+    if users.len() <= 2 {
+        div.add_span(Span::new(&users.join(",")).foreground(Color::Yellow));
+    } else {
+        div.add_span(Span::new(&users.len().to_string()).foreground(Color::Yellow));
+    }
+    div
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scratch::ScratchDir;
+    use std::fs::write;
+
+    #[test]
+    fn reboot_required_packages() {
+        let dir = ScratchDir::new("reboot");
+        let flag = dir.join("reboot-required");
+        assert_eq!(read_reboot_required(&flag), None);
+        write(&flag, "*** System restart required ***\n").unwrap();
+        assert_eq!(read_reboot_required(&flag), Some(RebootReason::Packages(0)));
+        write(
+            dir.join("reboot-required.pkgs"),
+            "linux-base\nlibc6\n\nlinux-base\n",
+        )
+        .unwrap();
+        assert_eq!(read_reboot_required(&flag), Some(RebootReason::Packages(2)));
+        assert_eq!(
+            format_reboot_required(&RebootReason::Packages(2)).text(),
+            "↻reboot 2"
+        );
+    }
+
+    #[test]
+    fn other_users() {
+        let users = |names: &[&str]| names.iter().map(|n| n.to_string()).collect::<Vec<String>>();
+        assert_eq!(format_other_users(&users(&["alice"])).text(), "⚇alice");
+        assert_eq!(
+            format_other_users(&users(&["alice", "bob"])).text(),
+            "⚇alice,bob"
+        );
+        assert_eq!(
+            format_other_users(&users(&["alice", "bob", "carol"])).text(),
+            "⚇3"
+        );
+    }
+}
@@ -0,0 +1,166 @@
+/*
+ * Promptly: show a prompt, sooner.
+ * Copyright (C) 2017  Terrence Cole
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+use crate::layout::{Color, Span};
+use std::{env::var, fs::read_to_string};
+use users::{
+    get_current_gid, get_current_uid, get_effective_gid, get_effective_uid, get_group_by_gid,
+    get_user_by_uid,
+};
+
+// The kernel's audit subsystem records who logged in, and keeps it across su and
+// sudo. Sessions that never logged in, like early boot services, get this.
+const UNSET_LOGIN_UID: u32 = u32::MAX;
+
+// Who the shell is running as, and who it was before sudo or su, if anyone.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Identity {
+    pub user: String,
+    pub uid: u32,
+    pub original_user: Option<String>,
+    // The effective group, when it is not the one the user would normally have.
+    pub group: Option<String>,
+}
+
+fn user_name(uid: u32) -> Option<String> {
+    get_user_by_uid(uid).map(|u| u.name().to_string_lossy().to_string())
+}
+
+impl Identity {
+    pub fn find() -> Self {
+        let uid = get_effective_uid();
+        let user = user_name(uid).unwrap_or_else(|| "<unknown_user>".to_owned());
+
+        // sudo says who ran it; su and setuid programs leave the real uid or the
+        // login uid behind.
+        let login_uid = read_to_string("/proc/self/loginuid")
+            .ok()
+            .and_then(|s| s.trim().parse::<u32>().ok())
+            .filter(|&id| id != UNSET_LOGIN_UID);
+        let original_user = var("SUDO_USER").ok().or_else(|| {
+            [Some(get_current_uid()), login_uid]
+                .into_iter()
+                .flatten()
+                .find(|&id| id != uid)
+                .and_then(user_name)
+        });
+
+        // newgrp and sg change both real and effective gid, so compare against
+        // the user's primary group as well.
+        let gid = get_effective_gid();
+        let primary_gid = get_user_by_uid(uid).map(|u| u.primary_group_id());
+        let group = (gid != get_current_gid() || primary_gid.is_some_and(|p| p != gid))
+            .then(|| get_group_by_gid(gid))
+            .flatten()
+            .map(|g| g.name().to_string_lossy().to_string());
+
+        Identity::new(user, uid, original_user, group)
+    }
+
+    // `sudo -u $USER` and the like switch to no one new.
+    fn new(user: String, uid: u32, original_user: Option<String>, group: Option<String>) -> Self {
+        Identity {
+            original_user: original_user.filter(|o| !o.is_empty() && *o != user),
+            user,
+            uid,
+            group,
+        }
+    }
+
+    pub fn is_root(&self) -> bool {
+        self.uid == 0
+    }
+
+    // The person at the keyboard, regardless of who they are acting as.
+    pub fn login_user(&self) -> &str {
+        self.original_user.as_deref().unwrap_or(&self.user)
+    }
+
+    // alice→root:wheel
+    pub fn spans(&self) -> Vec<Span> {
+        let mut spans = Vec::new();
+        if let Some(original) = &self.original_user {
+            spans.push(Span::new(original).foreground(Color::Blue).dimmed());
+            spans.push(Span::new("→").foreground(Color::White).dimmed());
+        }
+        let user = Span::new(&self.user);
+        spans.push(if self.is_root() {
+            user.foreground(Color::Red).bold()
+        } else if self.original_user.is_some() {
+            user.foreground(Color::Yellow).bold()
+        } else {
+            user.foreground(Color::Blue).dimmed()
+        });
+        if let Some(group) = &self.group {
+            spans.push(Span::new(":").foreground(Color::White).dimmed());
+            spans.push(Span::new(group).foreground(Color::Purple));
+        }
+        spans
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn identity(user: &str, uid: u32, original: Option<&str>, group: Option<&str>) -> Identity {
+        Identity::new(
+            user.to_owned(),
+            uid,
+            original.map(|o| o.to_owned()),
+            group.map(|g| g.to_owned()),
+        )
+    }
+
+    fn text(identity: &Identity) -> String {
+        identity
+            .spans()
+            .iter()
+            .map(|s| s.content.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn switched_users() {
+        let plain = identity("alice", 1000, None, None);
+        assert_eq!(text(&plain), "alice");
+        assert_eq!(
+            plain.spans(),
+            vec![Span::new("alice").foreground(Color::Blue).dimmed()]
+        );
+
+        let sudo = identity("root", 0, Some("alice"), None);
+        assert_eq!(text(&sudo), "alice→root");
+        assert_eq!(sudo.login_user(), "alice");
+        assert_eq!(
+            sudo.spans()[2],
+            Span::new("root").foreground(Color::Red).bold()
+        );
+
+        let su = identity("deploy", 1001, Some("alice"), Some("wheel"));
+        assert_eq!(text(&su), "alice→deploy:wheel");
+        assert_eq!(
+            su.spans()[2],
+            Span::new("deploy").foreground(Color::Yellow).bold()
+        );
+
+        let same = identity("alice", 1000, Some("alice"), None);
+        assert_eq!(same.original_user, None);
+        assert_eq!(text(&same), "alice");
+        assert_eq!(identity("alice", 1000, Some(""), None).original_user, None);
+    }
+}
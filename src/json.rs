@@ -0,0 +1,175 @@
+/*
+ * Promptly: show a prompt, sooner.
+ * Copyright (C) 2017  Terrence Cole
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+use std::{fs::read_to_string, path::Path, str::Chars};
+
+// Flatten a JSON document into (path, scalar) pairs, so that
+//
+//   {"subscriptions": [{"name": "dev", "isDefault": true}]}
+//
+// becomes `subscriptions.0.name = dev` and `subscriptions.0.isDefault = true`.
+// Strings are unescaped; numbers, booleans and null keep their literal text.
+// Malformed input yields whatever was read before the error.
+pub fn flatten(content: &str) -> Vec<(String, String)> {
+    let mut out = Vec::new();
+    let mut chars = content.trim_start_matches('\u{feff}').chars();
+    read_value(&mut chars, "", &mut out);
+    out
+}
+
+pub fn flatten_file(path: &Path) -> Option<Vec<(String, String)>> {
+    Some(flatten(&read_to_string(path).ok()?))
+}
+
+pub fn get<'a>(pairs: &'a [(String, String)], path: &str) -> Option<&'a str> {
+    pairs
+        .iter()
+        .find(|(p, _)| p == path)
+        .map(|(_, v)| v.as_str())
+}
+
+fn child(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_owned()
+    } else {
+        format!("{}.{}", path, key)
+    }
+}
+
+fn skip_whitespace(chars: &mut Chars) -> Option<char> {
+    loop {
+        let c = chars.clone().next()?;
+        if !c.is_whitespace() {
+            return Some(c);
+        }
+        chars.next();
+    }
+}
+
+fn read_value(chars: &mut Chars, path: &str, out: &mut Vec<(String, String)>) -> Option<()> {
+    match skip_whitespace(chars)? {
+        '{' => {
+            chars.next();
+            loop {
+                match skip_whitespace(chars)? {
+                    '}' => {
+                        chars.next();
+                        return Some(());
+                    }
+                    ',' => {
+                        chars.next();
+                    }
+                    '"' => {
+                        let key = read_string(chars)?;
+                        if skip_whitespace(chars)? != ':' {
+                            return None;
+                        }
+                        chars.next();
+                        read_value(chars, &child(path, &key), out)?;
+                    }
+                    _ => return None,
+                }
+            }
+        }
+        '[' => {
+            chars.next();
+            let mut index = 0;
+            loop {
+                match skip_whitespace(chars)? {
+                    ']' => {
+                        chars.next();
+                        return Some(());
+                    }
+                    ',' => {
+                        chars.next();
+                    }
+                    _ => {
+                        read_value(chars, &child(path, &index.to_string()), out)?;
+                        index += 1;
+                    }
+                }
+            }
+        }
+        '"' => {
+            let value = read_string(chars)?;
+            out.push((path.to_owned(), value));
+            Some(())
+        }
+        _ => {
+            let mut literal = String::new();
+            while let Some(c) = chars.clone().next() {
+                if c == ',' || c == '}' || c == ']' || c.is_whitespace() {
+                    break;
+                }
+                literal.push(c);
+                chars.next();
+            }
+            if literal.is_empty() {
+                return None;
+            }
+            out.push((path.to_owned(), literal));
+            Some(())
+        }
+    }
+}
+
+fn read_string(chars: &mut Chars) -> Option<String> {
+    chars.next();
+    let mut s = String::new();
+    loop {
+        match chars.next()? {
+            '"' => return Some(s),
+            '\\' => match chars.next()? {
+                'n' => s.push('\n'),
+                't' => s.push('\t'),
+                'r' => s.push('\r'),
+                'b' => s.push('\u{8}'),
+                'f' => s.push('\u{c}'),
+                'u' => {
+                    let hex = chars.by_ref().take(4).collect::<String>();
+                    s.push(
+                        u32::from_str_radix(&hex, 16)
+                            .ok()
+                            .and_then(char::from_u32)
+                            .unwrap_or('\u{fffd}'),
+                    );
+                }
+                c => s.push(c),
+            },
+            c => s.push(c),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nested_values() {
+        let pairs = flatten(
+            "\u{feff}{\"subscriptions\": [{\"name\": \"dev \\\"a\\\"\", \"isDefault\": false},
+              {\"name\": \"prod\", \"isDefault\": true, \"user\": {\"name\": \"me\"}}],
+              \"n\": -1.5e3, \"x\": null}",
+        );
+        assert_eq!(get(&pairs, "subscriptions.0.name"), Some("dev \"a\""));
+        assert_eq!(get(&pairs, "subscriptions.1.isDefault"), Some("true"));
+        assert_eq!(get(&pairs, "subscriptions.1.user.name"), Some("me"));
+        assert_eq!(get(&pairs, "n"), Some("-1.5e3"));
+        assert_eq!(get(&pairs, "x"), Some("null"));
+    }
+}
@@ -0,0 +1,245 @@
+/*
+ * Promptly: show a prompt, sooner.
+ * Copyright (C) 2017  Terrence Cole
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+use std::{fs::read_to_string, path::Path};
+
+// A reader for the flat subset that TOML and INI files share:
+//
+//   # comment
+//   top = "value"
+//   [section]
+//   key = 'value'   # trailing comment
+//   dotted.key = true
+//
+// Arrays and multi-line strings are skipped, even when they run over several
+// lines. Inline tables become dotted keys, so `version = { workspace = true }`
+// reads the same as `version.workspace = true`. That is all we need to pull a
+// name or version out of a config file without a real parser.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Entry {
+    pub section: String,
+    pub key: String,
+    pub value: String,
+}
+
+pub fn parse(content: &str) -> Vec<Entry> {
+    let mut section = String::new();
+    let mut out = Vec::new();
+    let mut lines = content.lines();
+    while let Some(line) = lines.next() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+            continue;
+        }
+        if let Some(header) = line.strip_prefix('[') {
+            section = header
+                .trim_start_matches('[')
+                .split(']')
+                .next()
+                .unwrap_or("")
+                .trim()
+                .trim_matches('"')
+                .to_owned();
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        let key = unquote(key.trim());
+        let value = value.trim();
+        if let Some(delim) = ["\"\"\"", "'''"].into_iter().find(|d| value.starts_with(d)) {
+            if !value[delim.len()..].contains(delim) {
+                lines.by_ref().find(|l| l.contains(delim));
+            }
+            continue;
+        }
+        if value.starts_with('[') {
+            let mut depth = bracket_depth(strip_comment(value));
+            while depth > 0
+                && let Some(line) = lines.next()
+            {
+                depth += bracket_depth(strip_comment(line));
+            }
+            continue;
+        }
+        if let Some(table) = value.strip_prefix('{') {
+            let table = strip_comment(table).trim_end().trim_end_matches('}');
+            for item in split_unquoted(table, ',') {
+                if let Some((k, v)) = item.split_once('=') {
+                    let v = v.trim();
+                    if !v.starts_with('[') && !v.starts_with('{') {
+                        out.push(Entry {
+                            section: section.clone(),
+                            key: format!("{}.{}", key, unquote(k.trim())),
+                            value: unquote(v).to_owned(),
+                        });
+                    }
+                }
+            }
+            continue;
+        }
+        out.push(Entry {
+            section: section.clone(),
+            key: key.to_owned(),
+            value: unquote(strip_comment(value)).to_owned(),
+        });
+    }
+    out
+}
+
+pub fn parse_file(path: &Path) -> Option<Vec<Entry>> {
+    Some(parse(&read_to_string(path).ok()?))
+}
+
+pub fn get<'a>(entries: &'a [Entry], section: &str, key: &str) -> Option<&'a str> {
+    entries
+        .iter()
+        .find(|e| e.section == section && e.key == key)
+        .map(|e| e.value.as_str())
+}
+
+fn strip_comment(value: &str) -> &str {
+    let mut quote = None;
+    for (i, c) in value.char_indices() {
+        match (quote, c) {
+            (None, '"') | (None, '\'') => quote = Some(c),
+            (Some(q), c) if q == c => quote = None,
+            (None, '#') => return value[..i].trim_end(),
+            _ => {}
+        }
+    }
+    value
+}
+
+// Net brackets opened on this line, ignoring any inside strings.
+fn bracket_depth(line: &str) -> i32 {
+    let mut quote = None;
+    let mut depth = 0;
+    for c in line.chars() {
+        match (quote, c) {
+            (None, '"') | (None, '\'') => quote = Some(c),
+            (Some(q), c) if q == c => quote = None,
+            (None, '[') => depth += 1,
+            (None, ']') => depth -= 1,
+            _ => {}
+        }
+    }
+    depth
+}
+
+// Split on `sep` where it is not inside a string, array or table.
+fn split_unquoted(s: &str, sep: char) -> Vec<&str> {
+    let mut quote = None;
+    let mut depth = 0;
+    let mut parts = Vec::new();
+    let mut start = 0;
+    for (i, c) in s.char_indices() {
+        match (quote, c) {
+            (None, '"') | (None, '\'') => quote = Some(c),
+            (Some(q), c) if q == c => quote = None,
+            (None, '[') | (None, '{') => depth += 1,
+            (None, ']') | (None, '}') => depth -= 1,
+            (None, c) if c == sep && depth == 0 => {
+                parts.push(&s[start..i]);
+                start = i + c.len_utf8();
+            }
+            _ => {}
+        }
+    }
+    parts.push(&s[start..]);
+    parts
+}
+
+fn unquote(s: &str) -> &str {
+    for q in ['"', '\''] {
+        if s.len() >= 2 && s.starts_with(q) && s.ends_with(q) {
+            return &s[1..s.len() - 1];
+        }
+    }
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sections_and_values() {
+        let entries = parse(
+            r#"
+            name = "top"
+            [package]
+            name = "promptly" # the name
+            version = '0.3.0'
+            keywords = ["prompt"]
+            edition.workspace = true
+            [overrides]
+            "/home/me/src" = "nightly"
+            "#,
+        );
+        assert_eq!(get(&entries, "", "name"), Some("top"));
+        assert_eq!(get(&entries, "package", "name"), Some("promptly"));
+        assert_eq!(get(&entries, "package", "version"), Some("0.3.0"));
+        assert_eq!(get(&entries, "package", "keywords"), None);
+        assert_eq!(get(&entries, "package", "edition.workspace"), Some("true"));
+        assert_eq!(get(&entries, "overrides", "/home/me/src"), Some("nightly"));
+    }
+
+    #[test]
+    fn multi_line_values() {
+        let entries = parse(
+            r#"
+            [package]
+            description = """
+            name = "not-the-name"
+            """
+            readme = '''
+            version = "9.9.9" '''
+            keywords = [
+                "a = b",  # ]
+                ["nested"],
+            ]
+            name = "real"
+            "#,
+        );
+        assert_eq!(get(&entries, "package", "name"), Some("real"));
+        assert_eq!(get(&entries, "package", "version"), None);
+        assert_eq!(get(&entries, "package", "description"), None);
+        assert_eq!(entries.len(), 1);
+    }
+
+    #[test]
+    fn inline_tables() {
+        let entries = parse(
+            r#"
+            [package]
+            version = { workspace = true }
+            edition = { workspace = true } # inherited
+            [dependencies]
+            git2 = { version = "0.20", features = ["a", "x=y"], default-features = false }
+            "#,
+        );
+        assert_eq!(get(&entries, "package", "version.workspace"), Some("true"));
+        assert_eq!(get(&entries, "package", "edition.workspace"), Some("true"));
+        assert_eq!(get(&entries, "dependencies", "git2.version"), Some("0.20"));
+        assert_eq!(get(&entries, "dependencies", "git2.features"), None);
+        assert_eq!(
+            get(&entries, "dependencies", "git2.default-features"),
+            Some("false")
+        );
+    }
+}
@@ -0,0 +1,371 @@
+/*
+ * Promptly: show a prompt, sooner.
+ * Copyright (C) 2017  Terrence Cole
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+use crate::{
+    alert::glob_match,
+    filesystem::{create_runtime_dir, mtime_key, read_private_file, write_private_file},
+    layout::{Color, Div, Span},
+};
+use std::{
+    env::var_os,
+    fs::read_to_string,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct KubeContext {
+    pub name: String,
+    pub namespace: Option<String>,
+}
+
+// Color the context when its name matches a glob, e.g. `prod-*=red`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct KubeContextColor {
+    pub pattern: String,
+    pub color: Color,
+}
+
+impl FromStr for KubeContextColor {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (pattern, color) = s
+            .rsplit_once('=')
+            .ok_or_else(|| format!("expected CONTEXT=COLOR, not '{}'", s))?;
+        Ok(KubeContextColor {
+            pattern: pattern.to_owned(),
+            color: color.parse()?,
+        })
+    }
+}
+
+// kubectl reads every file in $KUBECONFIG, or ~/.kube/config without it.
+fn kubeconfig_paths() -> Vec<PathBuf> {
+    match var_os("KUBECONFIG") {
+        Some(paths) => std::env::split_paths(&paths)
+            .filter(|p| !p.as_os_str().is_empty())
+            .collect(),
+        None => var_os("HOME")
+            .map(|home| PathBuf::from(home).join(".kube").join("config"))
+            .into_iter()
+            .collect(),
+    }
+}
+
+// Kubeconfig files can get large, so keep the answer around until one of the
+// files changes. The cache key lists every file with its mtime.
+pub fn find_kube_context() -> Option<KubeContext> {
+    let cache_path = create_runtime_dir()
+        .ok()
+        .map(|dir| dir.join("kube-context"));
+    find_kube_context_in(&kubeconfig_paths(), cache_path.as_deref())
+}
+
+fn find_kube_context_in(paths: &[PathBuf], cache_path: Option<&Path>) -> Option<KubeContext> {
+    let key = mtime_key(paths);
+    if let Some(cached) = cache_path.and_then(|p| read_private_file(p).ok()) {
+        let mut lines = cached.lines();
+        if lines.next() == Some(key.as_str()) {
+            let name = lines.next().filter(|n| !n.is_empty())?;
+            let namespace = lines.next().filter(|n| !n.is_empty());
+            return Some(KubeContext {
+                name: name.to_owned(),
+                namespace: namespace.map(|n| n.to_owned()),
+            });
+        }
+    }
+
+    let contents = paths
+        .iter()
+        .filter_map(|p| read_to_string(p).ok())
+        .collect::<Vec<String>>();
+    let context = merge_kubeconfigs(&contents);
+    if let Some(cache_path) = cache_path {
+        let (name, namespace) = match &context {
+            Some(ctx) => (ctx.name.as_str(), ctx.namespace.as_deref().unwrap_or("")),
+            None => ("", ""),
+        };
+        write_private_file(cache_path, &format!("{}\n{}\n{}\n", key, name, namespace)).ok();
+    }
+    context
+}
+
+// As kubectl merges: the first file to set current-context wins, as does the
+// first file to define a context with a given name.
+fn merge_kubeconfigs(contents: &[String]) -> Option<KubeContext> {
+    let parsed = contents
+        .iter()
+        .map(|c| parse_kubeconfig(c))
+        .collect::<Vec<Kubeconfig>>();
+    let current = parsed.iter().find_map(|k| k.current_context.clone())?;
+    let namespace = parsed
+        .iter()
+        .flat_map(|k| k.contexts.iter())
+        .find(|c| c.name == current)
+        .and_then(|c| c.namespace.clone());
+    Some(KubeContext {
+        name: current,
+        namespace,
+    })
+}
+
+#[derive(Default)]
+struct Kubeconfig {
+    current_context: Option<String>,
+    contexts: Vec<KubeContext>,
+}
+
+// Just enough YAML for the block style kubectl writes:
+//
+//   contexts:
+//   - context:
+//       cluster: dev
+//       namespace: web
+//     name: dev
+//   current-context: dev
+//
+// Items may carry lists of their own, such as `extensions:`, so only a dash at
+// the column of the first one starts a new context, and only keys at the item's
+// own column, or directly under its `context:`, are read.
+fn parse_kubeconfig(content: &str) -> Kubeconfig {
+    let mut config = Kubeconfig::default();
+    let mut in_contexts = false;
+    let mut list_indent: Option<usize> = None;
+    let mut item_indent = 0;
+    let mut in_context = false;
+    let mut context_indent: Option<usize> = None;
+    let mut item: Option<KubeContext> = None;
+    for raw_line in content.lines() {
+        let line = raw_line.split(" #").next().unwrap_or("").trim_end();
+        let trimmed = line.trim_start();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        let top_level = !line.starts_with(' ') && !line.starts_with('-');
+        if top_level {
+            finish_item(&mut item, &mut config);
+            in_contexts = trimmed.starts_with("contexts:");
+            list_indent = None;
+            if let Some(value) = trimmed.strip_prefix("current-context:") {
+                let value = unquote(value.trim());
+                if !value.is_empty() {
+                    config.current_context = Some(value.to_owned());
+                }
+            }
+            continue;
+        }
+        if !in_contexts {
+            continue;
+        }
+        let mut indent = line.len() - trimmed.len();
+        let mut field = trimmed;
+        if let Some(rest) = trimmed.strip_prefix("- ") {
+            let field_indent = line.len() - rest.trim_start().len();
+            if *list_indent.get_or_insert(indent) == indent {
+                finish_item(&mut item, &mut config);
+                item = Some(KubeContext {
+                    name: String::new(),
+                    namespace: None,
+                });
+                item_indent = field_indent;
+                in_context = false;
+            }
+            indent = field_indent;
+            field = rest.trim_start();
+        }
+        let (Some(ctx), Some((key, value))) = (item.as_mut(), field.split_once(':')) else {
+            continue;
+        };
+        let key = key.trim();
+        let value = unquote(value.trim()).to_owned();
+        if indent == item_indent {
+            match key {
+                "name" => ctx.name = value,
+                "context" => {
+                    in_context = true;
+                    context_indent = None;
+                }
+                _ => in_context = false,
+            }
+        } else if indent > item_indent
+            && in_context
+            && *context_indent.get_or_insert(indent) == indent
+            && key == "namespace"
+        {
+            ctx.namespace = Some(value);
+        }
+    }
+    finish_item(&mut item, &mut config);
+    config
+}
+
+fn finish_item(item: &mut Option<KubeContext>, config: &mut Kubeconfig) {
+    if let Some(ctx) = item.take()
+        && !ctx.name.is_empty()
+    {
+        config.contexts.push(ctx);
+    }
+}
+
+fn unquote(s: &str) -> &str {
+    s.trim_matches('"').trim_matches('\'')
+}
+
+pub fn format_kube_context(context: &KubeContext, colors: &[KubeContextColor]) -> Div {
+    let color = colors
+        .iter()
+        .find(|c| glob_match(&c.pattern, &context.name))
+        .map(|c| c.color.clone());
+    let mut div = Div::new_empty();
+    div.add_span(Span::new("@").foreground(Color::Yellow));
+    div.add_span(Span::new("k8s").foreground(Color::Cyan));
+    div.add_span(Span::new("{").bold());
+    div.add_span(match color {
+        Some(color) => Span::new(&context.name).foreground(color).bold().reverse(),
+        None => Span::new(&context.name).foreground(Color::Yellow).bold(),
+    });
+    if let Some(namespace) = &context.namespace {
+        div.add_span(Span::new(":").foreground(Color::White).dimmed());
+        div.add_span(Span::new(namespace).foreground(Color::Green));
+    }
+    div.add_span(Span::new("}").bold());
+    div
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scratch::ScratchDir;
+    use std::{
+        fs::{File, write},
+        time::{Duration, SystemTime},
+    };
+
+    const DEV: &str = r#"
+apiVersion: v1
+clusters:
+- cluster:
+    server: https://dev.example.com
+  name: dev
+contexts:
+- context:
+    cluster: dev
+    namespace: web
+    user: me
+  name: dev
+- context:
+    cluster: prod
+    user: me
+  name: "prod"
+current-context: dev
+kind: Config
+users:
+- name: me
+"#;
+
+    const PROD: &str = r#"
+contexts:
+  - name: prod
+    context:
+      cluster: prod
+      namespace: payments
+current-context: prod
+"#;
+
+    #[test]
+    fn single_file() {
+        let config = parse_kubeconfig(DEV);
+        assert_eq!(config.current_context.as_deref(), Some("dev"));
+        assert_eq!(config.contexts.len(), 2);
+        assert_eq!(config.contexts[0].namespace.as_deref(), Some("web"));
+        assert_eq!(config.contexts[1].name, "prod");
+    }
+
+    #[test]
+    fn nested_lists() {
+        let config = parse_kubeconfig(
+            r#"
+contexts:
+- context:
+    cluster: dev
+    extensions:
+    - name: tooling
+      extension:
+        namespace: tools
+    namespace: web
+  extensions:
+  - name: audit
+  name: dev
+- name: prod
+  context:
+    cluster: prod
+current-context: dev
+"#,
+        );
+        assert_eq!(
+            config.contexts,
+            vec![
+                KubeContext {
+                    name: "dev".to_owned(),
+                    namespace: Some("web".to_owned()),
+                },
+                KubeContext {
+                    name: "prod".to_owned(),
+                    namespace: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn cached_context() {
+        let dir = ScratchDir::new("kube");
+        let config = dir.join("config");
+        let cache = dir.join("kube-context");
+        write(&config, DEV).unwrap();
+        let paths = [config.clone()];
+        let found = || find_kube_context_in(&paths, Some(&cache)).map(|c| c.name);
+        assert_eq!(found().as_deref(), Some("dev"));
+
+        // While the file keeps its mtime, the cached answer stands.
+        let cached = read_to_string(&cache).unwrap();
+        let key = cached.lines().next().unwrap();
+        write(&cache, format!("{}\nstale\n\n", key)).unwrap();
+        assert_eq!(found().as_deref(), Some("stale"));
+
+        // `kubectl config use-context` rewrites the file.
+        write(&config, PROD).unwrap();
+        File::options()
+            .write(true)
+            .open(&config)
+            .unwrap()
+            .set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(1))
+            .unwrap();
+        assert_eq!(found().as_deref(), Some("prod"));
+    }
+
+    #[test]
+    fn merged_files() {
+        let ctx = merge_kubeconfigs(&[PROD.to_owned(), DEV.to_owned()]).unwrap();
+        assert_eq!(ctx.name, "prod");
+        assert_eq!(ctx.namespace.as_deref(), Some("payments"));
+        let ctx = merge_kubeconfigs(&[DEV.to_owned(), PROD.to_owned()]).unwrap();
+        assert_eq!(ctx.name, "dev");
+        assert_eq!(ctx.namespace.as_deref(), Some("web"));
+    }
+}
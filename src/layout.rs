@@ -15,7 +15,7 @@
  * You should have received a copy of the GNU General Public License
  * along with this program.  If not, see <http://www.gnu.org/licenses/>.
  */
-use std::{cmp, collections::HashSet, slice::Iter};
+use std::{cmp, collections::HashSet, slice::Iter, str::FromStr};
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum Color {
@@ -48,6 +48,24 @@ impl Color {
     }
 }
 
+impl FromStr for Color {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.to_lowercase().as_str() {
+            "black" => Color::Black,
+            "red" => Color::Red,
+            "green" => Color::Green,
+            "yellow" => Color::Yellow,
+            "blue" => Color::Blue,
+            "purple" | "magenta" => Color::Purple,
+            "cyan" => Color::Cyan,
+            "white" => Color::White,
+            _ => return Err(format!("unknown color '{}'", s)),
+        })
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub enum Style {
     Bold = 1,
@@ -72,6 +90,8 @@ pub struct Span {
     foreground: Option<Color>,
     background: Option<Color>,
     styles: HashSet<Style>,
+    raw_style: Option<String>,
+    link: Option<String>,
 }
 
 impl Span {
@@ -81,6 +101,8 @@ impl Span {
             foreground: None,
             background: None,
             styles: HashSet::new(),
+            raw_style: None,
+            link: None,
         }
     }
 
@@ -147,13 +169,63 @@ impl Span {
         self
     }
 
+    // Append pre-encoded SGR parameters, e.g. "01;34" from LS_COLORS, after any
+    // styles or colors set on the span.
+    pub fn raw_style(mut self, sgr: &str) -> Self {
+        self.raw_style = Some(sgr.to_owned());
+        self
+    }
+
+    // Make the span an OSC 8 hyperlink. The escapes take up no columns, so this
+    // does not change the span's width.
+    pub fn link(mut self, url: &str) -> Self {
+        self.link = Some(url.to_owned());
+        self
+    }
+
+    pub fn get_link(&self) -> Option<&str> {
+        self.link.as_deref()
+    }
+
+    // Open a hyperlink to `url`, or close the open one if `url` is None. We end the
+    // OSC with BEL instead of ST, since bash would eat the backslash in ESC \.
+    pub fn format_link(url: Option<&str>, escape_for_readline: bool) -> String {
+        let url = Self::escape_text(url.unwrap_or(""), escape_for_readline);
+        Self::make_readline_safe(&format!("\x1B]8;;{}\x07", url), escape_for_readline)
+    }
+
+    // Bash decodes backslash escapes in PS1 and then expands it like a double
+    // quoted string, so text from branch names, manifests and the like could run
+    // commands via $(...) or backticks. Escape it for both passes.
+    pub fn escape_text(s: &str, escape_for_readline: bool) -> String {
+        if !escape_for_readline {
+            return s.to_owned();
+        }
+        let mut out = String::with_capacity(s.len());
+        for c in s.chars() {
+            match c {
+                '\\' => out.push_str("\\\\\\\\"),
+                '$' | '`' => {
+                    out.push_str("\\\\");
+                    out.push(c);
+                }
+                c => out.push(c),
+            }
+        }
+        out
+    }
+
     #[allow(dead_code)]
     pub fn get_reset_style(escape_for_readline: bool) -> String {
         Self::make_readline_safe("\x1B[0m", escape_for_readline)
     }
 
     pub fn format_style(&self, escape_for_readline: bool) -> String {
-        if self.foreground.is_none() && self.background.is_none() && self.styles.is_empty() {
+        if self.foreground.is_none()
+            && self.background.is_none()
+            && self.styles.is_empty()
+            && self.raw_style.is_none()
+        {
             return "".to_owned();
         }
         let mut style = self
@@ -175,6 +247,7 @@ impl Span {
                 .map(|c| format!("{}", c.encode_foreground()))
                 .collect::<Vec<String>>(),
         );
+        style.extend(self.raw_style.iter().cloned());
         Self::make_readline_safe(
             &("\x1B[".to_owned() + &style.join(";") + "m"),
             escape_for_readline,
@@ -210,10 +283,21 @@ impl Div {
         self.children.push(span);
     }
 
+    // Link every span currently in the div to `url`.
+    pub fn link(mut self, url: &str) -> Self {
+        self.children = self.children.into_iter().map(|s| s.link(url)).collect();
+        self
+    }
+
     pub fn width(&self) -> usize {
         self.children.iter().map(|s| s.width()).sum()
     }
 
+    // The div's content without any styling.
+    pub fn text(&self) -> String {
+        self.children.iter().map(|s| s.content.as_str()).collect()
+    }
+
     pub fn iter_spans(&self) -> Iter<'_, Span> {
         self.children.iter()
     }
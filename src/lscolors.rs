@@ -0,0 +1,143 @@
+/*
+ * Promptly: show a prompt, sooner.
+ * Copyright (C) 2017  Terrence Cole
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+use std::{
+    collections::HashMap,
+    env::var,
+    fs::{metadata, symlink_metadata},
+    os::unix::fs::{MetadataExt, PermissionsExt},
+    path::Path,
+};
+
+// The values GNU ls uses when LS_COLORS does not mention a type.
+const DEFAULTS: &[(&str, &str)] = &[
+    ("di", "01;34"),
+    ("ln", "01;36"),
+    ("or", "40;31;01"),
+    ("st", "37;44"),
+    ("ow", "34;42"),
+    ("tw", "30;42"),
+];
+
+pub struct LsColors {
+    entries: HashMap<String, String>,
+}
+
+impl LsColors {
+    pub fn from_env() -> Self {
+        Self::parse(&var("LS_COLORS").unwrap_or_default())
+    }
+
+    pub fn parse(ls_colors: &str) -> Self {
+        let mut entries = DEFAULTS
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect::<HashMap<String, String>>();
+        for entry in ls_colors.split(':') {
+            if let Some((key, value)) = entry.split_once('=') {
+                entries.insert(key.to_owned(), value.to_owned());
+            }
+        }
+        LsColors { entries }
+    }
+
+    fn get(&self, key: &str) -> Option<&str> {
+        match self.entries.get(key).map(|s| s.as_str()) {
+            None | Some("") | Some("0") | Some("00") => None,
+            Some(value) => Some(value),
+        }
+    }
+
+    // Classify a directory on the path the way ls would. We also honor the
+    // "mp" key for mount points, as used by eza, since ls has no equivalent.
+    pub fn style_for_path(&self, path: &Path) -> Option<&str> {
+        let link_meta = symlink_metadata(path).ok()?;
+        if link_meta.file_type().is_symlink() {
+            return match (self.entries.get("ln").map(|s| s.as_str()), metadata(path)) {
+                (_, Err(_)) => self.get("or").or_else(|| self.get("ln")),
+                (Some("target"), Ok(_)) => self.style_for_directory(path),
+                (_, Ok(_)) => self.get("ln"),
+            };
+        }
+        self.style_for_directory(path)
+    }
+
+    fn style_for_directory(&self, path: &Path) -> Option<&str> {
+        let meta = metadata(path).ok()?;
+        let mode = meta.permissions().mode();
+        let sticky = mode & 0o1000 != 0;
+        let other_writable = mode & 0o002 != 0;
+        if sticky && other_writable {
+            return self.get("tw").or_else(|| self.get("di"));
+        }
+        if other_writable {
+            return self.get("ow").or_else(|| self.get("di"));
+        }
+        if sticky {
+            return self.get("st").or_else(|| self.get("di"));
+        }
+        if let Some(parent) = path.parent()
+            && let Ok(parent_meta) = metadata(parent)
+            && parent_meta.dev() != meta.dev()
+        {
+            return self.get("mp").or_else(|| self.get("di"));
+        }
+        self.get("di")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_overrides_defaults() {
+        let colors = LsColors::parse("rs=0:di=01;33:ln=target:*.rs=32");
+        assert_eq!(colors.get("di"), Some("01;33"));
+        assert_eq!(colors.get("tw"), Some("30;42"));
+        assert_eq!(colors.get("rs"), None);
+        assert_eq!(colors.get("*.rs"), Some("32"));
+    }
+
+    #[test]
+    fn directory_kinds() {
+        use crate::scratch::ScratchDir;
+        use std::{
+            fs::{Permissions, create_dir, set_permissions},
+            os::unix::fs::symlink,
+        };
+        let root = ScratchDir::new("ls");
+        let style = |name: &str, ls_colors: &str| {
+            LsColors::parse(ls_colors)
+                .style_for_path(&root.join(name))
+                .map(|s| s.to_owned())
+        };
+        for (name, mode) in [("tw", 0o1777), ("ow", 0o777), ("st", 0o1755), ("di", 0o755)] {
+            create_dir(root.join(name)).unwrap();
+            set_permissions(root.join(name), Permissions::from_mode(mode)).unwrap();
+        }
+        symlink(root.join("di"), root.join("ln")).unwrap();
+        symlink(root.join("missing"), root.join("or")).unwrap();
+        assert_eq!(style("tw", "").as_deref(), Some("30;42"));
+        assert_eq!(style("ow", "").as_deref(), Some("34;42"));
+        assert_eq!(style("st", "").as_deref(), Some("37;44"));
+        assert_eq!(style("di", "").as_deref(), Some("01;34"));
+        assert_eq!(style("ln", "").as_deref(), Some("01;36"));
+        assert_eq!(style("ln", "ln=target").as_deref(), Some("01;34"));
+        assert_eq!(style("or", "").as_deref(), Some("40;31;01"));
+    }
+}
@@ -15,26 +15,54 @@
  * You should have received a copy of the GNU General Public License
  * along with this program.  If not, see <http://www.gnu.org/licenses/>.
  */
+mod alert;
+mod battery;
+mod cargo;
+mod cloud;
+mod distro;
+mod filesystem;
+mod host;
+mod identity;
+mod json;
+mod keyvalue;
+mod kube;
 mod layout;
+mod lscolors;
+mod network;
+mod nix;
+mod python;
 mod render;
+#[cfg(test)]
+mod scratch;
+mod session;
+mod status;
+mod sysload;
+mod thermal;
+mod timing;
+mod toolchains;
 
+use alert::{AlertContext, AlertRule};
 use layout::{Color, Div, Layout, LayoutOptions, Span};
-use render::Run;
+use lscolors::LsColors;
+use render::{Notification, Run};
+use status::ExitStatus;
 
-use chrono::Local;
+use chrono::{Local, Utc};
 use clap::{
-    Parser,
+    Args as ClapArgs, Parser, Subcommand, ValueEnum,
     builder::{Styles, styling::AnsiColor},
 };
 use failure::Fallible;
 use git2::Repository;
 use hostname::get;
 use std::{
-    env::{current_dir, var},
-    path::PathBuf,
-    time::Instant,
+    env::{current_dir, var, var_os},
+    fs::metadata,
+    os::unix::{ffi::OsStrExt, fs::MetadataExt, process::parent_id},
+    path::{Component, Path, PathBuf},
+    time::{Duration, Instant},
 };
-use users::{get_current_username, get_effective_uid};
+use users::{get_effective_uid, get_user_by_uid};
 
 const STYLES: Styles = Styles::styled()
     .header(AnsiColor::Yellow.on_default())
@@ -43,15 +71,48 @@ const STYLES: Styles = Styles::styled()
     .placeholder(AnsiColor::Cyan.on_default());
 
 #[derive(Parser)]
-#[command(version, about, styles = STYLES)]
+#[command(
+    version,
+    about,
+    styles = STYLES,
+    args_conflicts_with_subcommands = true
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    #[command(flatten)]
+    args: Option<Args>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Record that a command is starting; call from the shell's preexec hook
+    Start {
+        /// The shell's pid, if promptly is not run directly by the shell
+        #[clap(long, value_name = "PID")]
+        pid: Option<u32>,
+    },
+}
+
+#[derive(ClapArgs)]
 struct Args {
-    /// Prior command exit code
-    #[clap(short, long, value_name = "CODE")]
-    status: String,
+    /// Prior command exit code, or one code per pipeline stage, e.g. "${PIPESTATUS[*]}"
+    #[clap(short, long, value_name = "CODES")]
+    status: ExitStatus,
+
+    /// The shell's $?, which wins over --status when they disagree, e.g. after `! grep`
+    #[clap(long, value_name = "CODE")]
+    last_status: Option<i32>,
+
+    /// Prior command run time, in seconds or as a duration like 1m30s or 250ms.
+    /// If omitted, the time since `promptly start` is used instead.
+    #[clap(short, long, value_name = "DURATION", value_parser = timing::parse_duration)]
+    time: Option<Duration>,
 
-    /// Prior command run time
-    #[clap(short, long, value_name = "SECONDS")]
-    time: i32,
+    /// The shell's pid, if promptly is not run directly by the shell
+    #[clap(long, value_name = "PID")]
+    pid: Option<u32>,
 
     /// The terminal width to render with
     #[clap(short, long, value_name = "COLUMNS")]
@@ -73,6 +134,164 @@ struct Args {
     #[clap(long, value_name = "PATH")]
     alternate_home: Option<PathBuf>,
 
+    /// Do not mark read-only or foreign-owned directories on the path
+    #[clap(long)]
+    no_path_badges: bool,
+
+    /// Color each directory on the path as ls would, using $LS_COLORS
+    #[clap(long)]
+    ls_colors: bool,
+
+    /// How to look for git repositories on network and FUSE filesystems
+    #[clap(long, value_name = "MODE", default_value = "skip")]
+    remote_git: RemoteGit,
+
+    /// Switch to the alert theme when a rule matches: path:DIR, host:GLOB,
+    /// user:GLOB, root or switched (after sudo or su). Rules are checked in order; the
+    /// first match wins.
+    #[clap(long = "alert", value_name = "RULE")]
+    alert_rules: Vec<AlertRule>,
+
+    /// The border and arrow color used by the alert theme
+    #[clap(long, value_name = "COLOR", default_value = "red")]
+    alert_color: Color,
+
+    /// Show this text in a banner while the alert theme is active
+    #[clap(long, value_name = "TEXT")]
+    alert_banner: Option<String>,
+
+    /// Make the path and git branch clickable with OSC 8 hyperlinks
+    #[clap(long)]
+    hyperlinks: bool,
+
+    /// Notify the terminal when a command runs longer than --notify-after
+    #[clap(long, value_name = "METHOD")]
+    notify: Option<NotifyMethod>,
+
+    /// How long a command must run before we notify
+    #[clap(long, value_name = "DURATION", default_value = "30s", value_parser = timing::parse_duration)]
+    notify_after: Duration,
+
+    /// Do not show the active Python virtualenv, conda or pyenv environment
+    #[clap(long)]
+    no_python: bool,
+
+    /// Do not show the nix shell and direnv badge
+    #[clap(long)]
+    no_nix: bool,
+
+    /// Do not show the Cargo package and rust toolchain
+    #[clap(long)]
+    no_rust: bool,
+
+    /// Do not show tool versions pinned by go.mod, .tool-versions, .nvmrc and friends
+    #[clap(long)]
+    no_toolchains: bool,
+
+    /// Show the current Kubernetes context and namespace
+    #[clap(long)]
+    show_kube: bool,
+
+    /// Color a Kubernetes context matching GLOB, e.g. 'prod-*=red'; may be repeated
+    #[clap(long = "kube-context-color", value_name = "GLOB=COLOR")]
+    kube_context_colors: Vec<kube::KubeContextColor>,
+
+    /// Show the active AWS profile, gcloud configuration and Azure subscription
+    #[clap(long)]
+    show_cloud: bool,
+
+    /// Warn when cloud credentials expire within this long
+    #[clap(long, value_name = "DURATION", default_value = "15m", value_parser = timing::parse_duration)]
+    cloud_expiry_warning: Duration,
+
+    /// Do not show the battery float
+    #[clap(long)]
+    no_battery: bool,
+
+    /// Show the battery when charge is at or below this percentage, even on AC power
+    #[clap(long, value_name = "PERCENT", default_value = "20")]
+    battery_threshold: u64,
+
+    /// Show load, available memory and uptime when any of them is out of the ordinary
+    #[clap(long)]
+    show_load: bool,
+
+    /// Warn when the one minute load average per CPU reaches this
+    #[clap(long, value_name = "RATIO", default_value = "1.0")]
+    load_warning: f64,
+
+    /// Warn when available memory drops to this percentage
+    #[clap(long, value_name = "PERCENT", default_value = "10")]
+    memory_warning: u64,
+
+    /// Warn when the machine has been up this long
+    #[clap(long, value_name = "DURATION", default_value = "30d", value_parser = timing::parse_duration)]
+    uptime_warning: Duration,
+
+    /// Show the CPU temperature and any Raspberry Pi throttling or under-voltage
+    #[clap(long)]
+    show_temperature: bool,
+
+    /// Color the temperature yellow from this many degrees Celsius, and red from ten more
+    #[clap(long, value_name = "CELSIUS", default_value = "70")]
+    temperature_warning: f64,
+
+    /// Do not warn when the disk holding the current directory is filling up
+    #[clap(long)]
+    no_disk: bool,
+
+    /// Warn when the disk holding the current directory is this percent full
+    #[clap(long, value_name = "PERCENT", default_value = "90")]
+    disk_used_warning: u64,
+
+    /// Warn when less than this much space is left, e.g. 2G
+    #[clap(long, value_name = "SIZE", default_value = "1G", value_parser = filesystem::parse_size)]
+    disk_free_warning: u64,
+
+    /// Show a badge when an installed update is waiting for a reboot
+    #[clap(long)]
+    show_reboot: bool,
+
+    /// Show a badge naming other users who are logged in
+    #[clap(long)]
+    show_users: bool,
+
+    /// Show the distribution, its version and the CPU architecture
+    #[clap(long)]
+    show_os: bool,
+
+    /// Color a distribution by its os-release ID, e.g. 'nixos=purple'; may be repeated
+    #[clap(long = "distro-color", value_name = "ID=COLOR")]
+    distro_colors: Vec<distro::DistroColor>,
+
+    /// The number of running background jobs
+    #[clap(long, value_name = "COUNT", default_value = "0")]
+    jobs: usize,
+
+    /// The number of stopped jobs
+    #[clap(long, value_name = "COUNT", default_value = "0")]
+    stopped_jobs: usize,
+
+    /// The number of directories pushed onto the directory stack
+    #[clap(long, value_name = "DEPTH", default_value = "0")]
+    dirstack: usize,
+
+    /// Do not count nested shells from $SHLVL
+    #[clap(long)]
+    no_shell_level: bool,
+
+    /// Only show the username when not in an ssh session, container or chroot
+    #[clap(long)]
+    hide_local_host: bool,
+
+    /// Show this machine's IP addresses, and where
+    #[clap(long, value_name = "PLACEMENT")]
+    show_ip: Option<ShowIp>,
+
+    /// Only show addresses on interfaces matching GLOB or within CIDR; may be repeated
+    #[clap(long = "ip-filter", value_name = "GLOB|CIDR")]
+    ip_filters: Vec<network::AddressFilter>,
+
     /// Print out timings after the prompt
     #[clap(long)]
     show_timings: bool,
@@ -82,41 +301,273 @@ struct Args {
     verbose: bool,
 }
 
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum RemoteGit {
+    /// Do not look for a repository at all
+    Skip,
+    /// Only look in the current directory, not its parents
+    Shallow,
+    /// Look the same way as on a local filesystem
+    Full,
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum ShowIp {
+    /// In a float of their own
+    Float,
+    /// In place of the hostname
+    Host,
+    /// After the hostname
+    Alongside,
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum NotifyMethod {
+    /// Ring the terminal bell
+    Bell,
+    /// Send a desktop notification with OSC 9 (iTerm2, WezTerm, Windows Terminal)
+    Osc9,
+    /// Send a desktop notification with OSC 777 (rxvt, foot, VTE-based terminals)
+    Osc777,
+}
+
 fn main() -> Fallible<()> {
-    let args = Args::parse();
+    let cli = Cli::parse();
+    if let Some(Command::Start { pid }) = cli.command {
+        return timing::record_start(pid.unwrap_or_else(parent_id));
+    }
+    let mut args = cli
+        .args
+        .expect("clap requires the prompt arguments without a subcommand");
+    if let Some(code) = args.last_status {
+        args.status = args.status.reconcile(code);
+    }
 
     let timed = args.show_timings;
+    let t0 = get_time(timed);
     let columns = args.width;
-    let prior_runtime_seconds = args.time;
+    let prior_runtime_duration = args.time.unwrap_or_else(|| {
+        timing::take_elapsed(args.pid.unwrap_or_else(parent_id)).unwrap_or_default()
+    });
 
-    let border_template = if args.status == "0" {
-        Span::new("").foreground(Color::Blue).bold()
-    } else {
-        Span::new("").foreground(Color::Red).bold()
-    };
-    let prompt_template = Span::new("").foreground(Color::Green).dimmed();
-    let prior_runtime = format_run_time(prior_runtime_seconds);
+    let cwd = current_dir()?;
+    let logical_cwd = logical_path(&cwd, var_os("PWD").map(PathBuf::from));
+    let identity = identity::Identity::find();
+    let hostname = find_hostname();
+    let alert = AlertRule::find_first(
+        &args.alert_rules,
+        &AlertContext {
+            cwd: &cwd,
+            logical_cwd: &logical_cwd,
+            hostname: &hostname,
+            username: &identity.user,
+            effective_uid: identity.uid,
+            original_user: identity.original_user.as_deref(),
+        },
+    );
+    if args.verbose
+        && let Some(rule) = alert
+    {
+        println!("alert:       {}", rule);
+    }
+
+    let mut border_template = Span::new("")
+        .foreground(args.status.class().border_color())
+        .bold();
+    let mut prompt_template = Span::new("").foreground(Color::Green).dimmed();
+    if alert.is_some() {
+        border_template = Span::new("").foreground(args.alert_color.clone()).bold();
+        prompt_template = Span::new("").foreground(args.alert_color.clone()).bold();
+    }
+    let prior_runtime = format_run_time(prior_runtime_duration);
+    let notification =
+        match args.notify {
+            Some(method) if prior_runtime_duration >= args.notify_after => Some(
+                build_notification(method, &args.status, &prior_runtime.text()),
+            ),
+            _ => None,
+        };
 
     let mut left_floats = Vec::<Div>::new();
     let mut right_floats = Vec::<Div>::new();
 
+    if alert.is_some()
+        && let Some(banner) = &args.alert_banner
+    {
+        left_floats.push(format_alert_banner(banner, args.alert_color.clone()));
+    }
+
     let t1 = get_time(timed);
-    let path_div = format_path(args.alternate_home)?;
+    let remote_fs = filesystem::find_remote_fs(&cwd);
+    // Coloring symlinks on the path only works if we walk the path we came by.
+    let mut path_div = format_path(
+        if args.ls_colors { &logical_cwd } else { &cwd },
+        args.alternate_home,
+        args.ls_colors && remote_fs.is_none(),
+    )?;
+    if args.hyperlinks {
+        path_div = path_div.link(&encode_file_url(&hostname, &cwd));
+    }
+    if !args.no_path_badges {
+        add_path_badges(&mut path_div, &cwd, remote_fs.as_deref());
+    }
     left_floats.push(path_div);
-
     let t2 = get_time(timed);
-    let git_branch = find_git_branch();
-    if let Some(branch) = git_branch {
-        left_floats.push(format_git_branch(&branch));
+
+    let git_depth = match (&remote_fs, args.remote_git) {
+        (Some(_), RemoteGit::Skip) => 0,
+        (Some(_), RemoteGit::Shallow) => 1,
+        _ => GIT_SEARCH_PATHS.len(),
+    };
+    let git_head = find_git_head(git_depth);
+    if let Some(head) = git_head {
+        left_floats.push(format_git_branch(&head, args.hyperlinks));
     }
 
     let t3 = get_time(timed);
-    right_floats.push(format_date_time());
 
+    if !args.no_python
+        && let Some(env) = python::find_python_env()
+    {
+        left_floats.push(python::format_python_env(&env));
+    }
+
+    // Looking for an .envrc that direnv refused to load walks up the tree.
+    if !args.no_nix
+        && let Some(env) = nix::find_dev_env(&logical_cwd, remote_fs.is_none())
+    {
+        left_floats.push(nix::format_dev_env(&env));
+    }
+
+    // Walking up the tree is a stat per directory; skip it where that is slow.
+    if !args.no_rust
+        && remote_fs.is_none()
+        && let Some(project) = cargo::find_cargo_project(&cwd)
+    {
+        left_floats.push(cargo::format_cargo_project(&project));
+    }
+
+    if !args.no_toolchains && remote_fs.is_none() {
+        let home = var_os("HOME").map(PathBuf::from);
+        let toolchains = toolchains::find_toolchains(&cwd, home.as_deref());
+        if !toolchains.is_empty() {
+            left_floats.push(toolchains::format_toolchains(&toolchains));
+        }
+    }
+
+    if args.show_kube
+        && let Some(context) = kube::find_kube_context()
+    {
+        left_floats.push(kube::format_kube_context(
+            &context,
+            &args.kube_context_colors,
+        ));
+    }
+
+    if args.show_cloud {
+        let now = Utc::now();
+        for context in cloud::find_cloud_contexts() {
+            left_floats.push(cloud::format_cloud_context(
+                &context,
+                now,
+                args.cloud_expiry_warning,
+            ));
+        }
+    }
+
+    let shell_level = var("SHLVL")
+        .ok()
+        .and_then(|level| level.parse::<usize>().ok())
+        .filter(|_| !args.no_shell_level)
+        .unwrap_or(1);
+    left_floats.extend(format_shell_state(
+        args.jobs,
+        args.stopped_jobs,
+        args.dirstack,
+        shell_level,
+    ));
+
+    if !args.status.is_success() {
+        left_floats.push(format_exit_status(&args.status));
+    }
+
+    if !args.no_battery
+        && let Some(battery) = battery::find_battery()
+        && battery::should_show(&battery, args.battery_threshold)
+    {
+        right_floats.push(battery::format_battery(&battery));
+    }
+
+    if args.show_load {
+        let limits = sysload::SysLoadLimits {
+            load_per_cpu: args.load_warning,
+            memory_available: args.memory_warning,
+            uptime: args.uptime_warning,
+        };
+        if let Some(div) = sysload::format_sysload(&sysload::find_sysload(), &limits) {
+            right_floats.push(div);
+        }
+    }
+
+    if args.show_temperature
+        && let Some(thermal) = thermal::find_thermal()
+    {
+        right_floats.push(thermal::format_thermal(&thermal, args.temperature_warning));
+    }
+
+    if !args.no_disk
+        && let Some(usage) = filesystem::disk_usage(&cwd)
+        && (usage.used_percent >= args.disk_used_warning
+            || usage.available < args.disk_free_warning)
+    {
+        right_floats.push(format_disk_usage(&usage));
+    }
+
+    let session = session::Session::find();
+    if args.show_reboot
+        && let Some(reason) =
+            host::find_reboot_required(session.container.is_none() && session.chroot.is_none())
+    {
+        right_floats.push(host::format_reboot_required(&reason));
+    }
+    if args.show_users {
+        let users = host::find_other_users(identity.login_user());
+        if !users.is_empty() {
+            right_floats.push(host::format_other_users(&users));
+        }
+    }
+    if args.show_os
+        && let Some(distro) = distro::find_distro()
+    {
+        right_floats.push(distro::format_distro(&distro, &args.distro_colors));
+    }
+
+    let t4 = get_time(timed);
+    right_floats.push(format_date_time());
     let t5 = get_time(timed);
-    right_floats.push(format_user_host());
 
+    let addresses = match args.show_ip {
+        Some(_) => network::find_addresses(&args.ip_filters),
+        None => Vec::new(),
+    };
+    if args.show_ip == Some(ShowIp::Float) && !addresses.is_empty() {
+        right_floats.push(network::format_addresses(&addresses));
+    }
+
+    let show_host = !(args.hide_local_host && session.is_local())
+        && (args.show_ip != Some(ShowIp::Host) || addresses.is_empty());
+    let host_addresses = match args.show_ip {
+        Some(ShowIp::Host) | Some(ShowIp::Alongside) => addresses.as_slice(),
+        _ => &[],
+    };
+    right_floats.push(format_user_host(
+        &identity,
+        show_host.then_some(hostname.as_str()),
+        host_addresses,
+        &session,
+    ));
     let t6 = get_time(timed);
+
     let options = LayoutOptions::new()
         .verbose(args.verbose)
         .use_safe_arrow(args.safe_arrow)
@@ -130,16 +581,21 @@ fn main() -> Fallible<()> {
         None => Run::get_fallback_run(),
     };
     let t7 = get_time(timed);
-    Run::show_all(&runs, options.escape_for_readline);
+    Run::show_all(
+        &runs,
+        notification.as_ref(),
+        var("TMUX").is_ok(),
+        options.escape_for_readline,
+    );
     let t8 = get_time(timed);
     if timed {
         println!("Fmt Path:      {:?}", t2.unwrap() - t1.unwrap());
         println!("Fmt Git:       {:?}", t3.unwrap() - t2.unwrap());
-        println!("Fmt Date:      {:?}", t5.unwrap() - t3.unwrap());
+        println!("Fmt Date:      {:?}", t5.unwrap() - t4.unwrap());
         println!("Fmt User/Host: {:?}", t6.unwrap() - t5.unwrap());
         println!("Layout&Render: {:?}", t7.unwrap() - t6.unwrap());
         println!("Writing:       {:?}", t8.unwrap() - t7.unwrap());
-        println!("Total:         {:?}", t8.unwrap() - t1.unwrap());
+        println!("Total:         {:?}", t8.unwrap() - t0.unwrap());
     }
     Ok(())
 }
@@ -151,8 +607,7 @@ fn get_time(timed: bool) -> Option<Instant> {
     None
 }
 
-fn format_path(alt_home: Option<PathBuf>) -> Fallible<Div> {
-    let path = current_dir()?;
+fn format_path(path: &Path, alt_home: Option<PathBuf>, use_ls_colors: bool) -> Fallible<Div> {
     let raw_path_str = path.to_str().unwrap_or("<error>");
     let home_str = match alt_home {
         None => var("HOME")?,
@@ -163,58 +618,150 @@ fn format_path(alt_home: Option<PathBuf>) -> Fallible<Div> {
     } else {
         raw_path_str.to_owned()
     };
-    Ok(Div::new(Span::new(&path_str).bold()))
+    Ok(if use_ls_colors {
+        format_path_components(path, Path::new(&home_str))
+    } else {
+        Div::new(Span::new(&path_str).bold())
+    })
+}
+
+// The kernel only knows the physical directory; the shell's $PWD remembers the
+// symlinks we came through. Show that, as long as it is not stale.
+fn logical_path(cwd: &Path, pwd: Option<PathBuf>) -> PathBuf {
+    match pwd {
+        Some(pwd)
+            if pwd.is_absolute()
+                && pwd
+                    .components()
+                    .all(|c| matches!(c, Component::RootDir | Component::Normal(_)))
+                && filesystem::is_same_file(&pwd, cwd) =>
+        {
+            pwd
+        }
+        _ => cwd.to_path_buf(),
+    }
+}
+
+// Percent-encode everything but unreserved characters and the path separators.
+fn percent_encode(bytes: &[u8]) -> String {
+    let mut out = String::new();
+    for &b in bytes {
+        if b.is_ascii_alphanumeric() || b"/-._~".contains(&b) {
+            out.push(b as char);
+        } else {
+            out += &format!("%{:02X}", b);
+        }
+    }
+    out
+}
+
+fn encode_file_url(hostname: &str, path: &Path) -> String {
+    format!(
+        "file://{}{}",
+        hostname,
+        percent_encode(path.as_os_str().as_bytes())
+    )
+}
+
+fn format_path_components(path: &Path, home: &Path) -> Div {
+    let colors = LsColors::from_env();
+    let style_span = |name: &str, at: &Path| match colors.style_for_path(at) {
+        Some(sgr) => Span::new(name).raw_style(sgr),
+        None => Span::new(name).bold(),
+    };
+
+    let mut div = Div::new_empty();
+    let (mut current, rest) = match path.strip_prefix(home) {
+        Ok(rest) if home.has_root() => {
+            div.add_span(style_span("~", home));
+            (home.to_path_buf(), rest)
+        }
+        _ => {
+            div.add_span(style_span("/", Path::new("/")));
+            (PathBuf::from("/"), path.strip_prefix("/").unwrap_or(path))
+        }
+    };
+    for (i, component) in rest.iter().enumerate() {
+        if i > 0 || current != Path::new("/") {
+            div.add_span(Span::new("/").bold());
+        }
+        current.push(component);
+        div.add_span(style_span(&component.to_string_lossy(), &current));
+    }
+    div
+}
+
+fn add_path_badges(div: &mut Div, path: &Path, remote_fs: Option<&str>) {
+    let owner = metadata(path)
+        .map(|m| m.uid())
+        .unwrap_or_else(|_| get_effective_uid());
+    if owner != get_effective_uid() {
+        let name = get_user_by_uid(owner)
+            .map(|u| u.name().to_string_lossy().to_string())
+            .unwrap_or_else(|| owner.to_string());
+        div.add_span(Span::new(" ("));
+        div.add_span(Span::new(&name).foreground(Color::Yellow));
+        div.add_span(Span::new(")"));
+    }
+    if filesystem::is_read_only_mount(path) {
+        div.add_span(Span::new(" ro").foreground(Color::Red).bold());
+    } else if !filesystem::is_writable(path) {
+        div.add_span(Span::new(" ro").foreground(Color::Yellow).bold());
+    }
+    if let Some(fs_type) = remote_fs {
+        div.add_span(Span::new(" ⇅").foreground(Color::Cyan));
+        div.add_span(Span::new(fs_type).foreground(Color::Cyan).dimmed());
+    }
 }
 
-fn format_run_time(t: i32) -> Div {
+fn format_run_time(t: Duration) -> Div {
     let mut out = Div::new_empty();
-    if t == 0 {
+    let mut add_unit = |value: &str, unit: &str| {
+        out.add_span(Span::new(value).foreground(Color::Purple).bold());
+        out.add_span(Span::new(unit).foreground(Color::Purple).dimmed());
+    };
+
+    let ms = t.as_millis();
+    if ms == 0 {
         out.add_span(Span::new("ε").foreground(Color::Purple).bold());
         return out;
     }
-
-    let mut s = t;
-    if s > 3600 {
-        let h = s / 3600;
-        s -= 3600 * h;
-        out.add_span(
-            Span::new(&format!("{}", h))
-                .foreground(Color::Purple)
-                .bold(),
-        );
-        out.add_span(Span::new("h").foreground(Color::Purple).dimmed());
-    }
-    if s > 60 {
-        let m = s / 60;
-        s -= 60 * m;
-        out.add_span(
-            Span::new(&format!("{}", m))
-                .foreground(Color::Purple)
-                .bold(),
-        );
-        out.add_span(Span::new("m").foreground(Color::Purple).dimmed());
+    if ms < 1000 {
+        add_unit(&format!("{}", ms), "ms");
+        return out;
     }
-    if s > 0 {
-        out.add_span(
-            Span::new(&format!("{}", s))
-                .foreground(Color::Purple)
-                .bold(),
-        );
-        out.add_span(Span::new("s").foreground(Color::Purple).dimmed());
+    if ms < 10_000 {
+        add_unit(&format!("{}.{}", ms / 1000, ms % 1000 / 100), "s");
+        return out;
+    }
+
+    let mut s = t.as_secs();
+    for (unit, size) in [("d", 86400), ("h", 3600), ("m", 60), ("s", 1)] {
+        if s >= size {
+            add_unit(&format!("{}", s / size), unit);
+            s %= size;
+        }
     }
     out
 }
 
-fn find_git_branch() -> Option<String> {
-    for path in &[".", "..", "../..", "../../.."] {
-        if let Some(branch) = find_git_branch_at(path) {
-            return Some(branch);
+const GIT_SEARCH_PATHS: [&str; 4] = [".", "..", "../..", "../../.."];
+
+struct GitHead {
+    branch: String,
+    web_url: Option<String>,
+}
+
+fn find_git_head(depth: usize) -> Option<GitHead> {
+    for path in GIT_SEARCH_PATHS.iter().take(depth) {
+        if let Some(head) = find_git_head_at(path) {
+            return Some(head);
         }
     }
     None
 }
 
-fn find_git_branch_at(path: &'static str) -> Option<String> {
+fn find_git_head_at(path: &'static str) -> Option<GitHead> {
     let repo = match Repository::open(path) {
         Ok(repo) => repo,
         Err(_) => return None,
@@ -223,19 +770,126 @@ fn find_git_branch_at(path: &'static str) -> Option<String> {
         Ok(head) => head,
         Err(_) => return None,
     };
-    Some(head.shorthand().unwrap_or("(detached)").to_owned())
+    let web_url = repo
+        .find_remote("origin")
+        .ok()
+        .and_then(|remote| remote.url().ok().and_then(remote_to_web_url));
+    Some(GitHead {
+        branch: head.shorthand().unwrap_or("(detached)").to_owned(),
+        web_url,
+    })
+}
+
+// Turn the common clone url forms into the https url of the project page:
+//   git@github.com:user/repo.git -> https://github.com/user/repo
+//   ssh://git@host:22/user/repo  -> https://host/user/repo
+fn remote_to_web_url(remote: &str) -> Option<String> {
+    let remote = remote.trim_end_matches('/').trim_end_matches(".git");
+    let (host, path) = if let Some(rest) = remote
+        .strip_prefix("https://")
+        .or_else(|| remote.strip_prefix("http://"))
+        .or_else(|| remote.strip_prefix("ssh://"))
+        .or_else(|| remote.strip_prefix("git://"))
+    {
+        rest.split_once('/')?
+    } else if !remote.contains("://") {
+        remote.split_once(':')?
+    } else {
+        return None;
+    };
+    let host = host.rsplit('@').next()?;
+    let host = host.split(':').next()?;
+    Some(format!("https://{}/{}", host, path.trim_start_matches('/')))
 }
 
-fn format_git_branch(branch: &str) -> Div {
+fn format_git_branch(head: &GitHead, hyperlinks: bool) -> Div {
     let mut div = Div::new_empty();
+    let mut branch = Span::new(&head.branch).foreground(Color::Yellow).bold();
+    if hyperlinks && let Some(web_url) = &head.web_url {
+        branch = branch.link(&format!(
+            "{}/tree/{}",
+            web_url,
+            percent_encode(head.branch.as_bytes())
+        ));
+    }
     div.add_span(Span::new("@").foreground(Color::Yellow));
     div.add_span(Span::new("git").foreground(Color::Cyan));
     div.add_span(Span::new("{").bold());
-    div.add_span(Span::new(branch).foreground(Color::Yellow).bold());
+    div.add_span(branch);
     div.add_span(Span::new("}").bold());
     div
 }
 
+fn format_exit_status(status: &ExitStatus) -> Div {
+    let mut div = Div::new_empty();
+    for (i, &code) in status.codes.iter().enumerate() {
+        if i > 0 {
+            div.add_span(Span::new("|").foreground(Color::White).dimmed());
+        }
+        let text = ExitStatus::describe_code(code);
+        div.add_span(if status.is_failed(i) {
+            Span::new(&text)
+                .foreground(status::classify(code).border_color())
+                .bold()
+        } else {
+            Span::new(&text).foreground(Color::Green).dimmed()
+        });
+    }
+    div
+}
+
+// Each counter only shows up when there is something to say: any jobs at all, a
+// pushd or two, or a shell started from another shell.
+fn format_shell_state(
+    jobs: usize,
+    stopped: usize,
+    dirstack: usize,
+    shell_level: usize,
+) -> Vec<Div> {
+    let mut floats = Vec::new();
+    let mut counter = |icon: &str, count: usize, color: Color| {
+        let mut div = Div::new(Span::new(icon).foreground(color.clone()));
+        div.add_span(Span::new(&count.to_string()).foreground(color).bold());
+        floats.push(div);
+    };
+    if jobs > 0 {
+        counter("⚙", jobs, Color::Cyan);
+    }
+    // Stopped jobs are the ones that get lost when the shell exits.
+    if stopped > 0 {
+        counter("⏸", stopped, Color::Yellow);
+    }
+    if dirstack > 0 {
+        counter("≡", dirstack, Color::Blue);
+    }
+    if shell_level > 1 {
+        counter("↳", shell_level, Color::Purple);
+    }
+    floats
+}
+
+fn build_notification(method: NotifyMethod, status: &ExitStatus, runtime: &str) -> Notification {
+    let outcome = if status.is_success() {
+        "succeeded".to_owned()
+    } else {
+        let codes = status
+            .codes
+            .iter()
+            .map(|&c| ExitStatus::describe_code(c))
+            .collect::<Vec<String>>();
+        format!("failed ({})", codes.join("|"))
+    };
+    let body = format!("Command {} after {}", outcome, runtime);
+    match method {
+        NotifyMethod::Bell => Notification::Bell,
+        NotifyMethod::Osc9 => Notification::Osc9(body),
+        NotifyMethod::Osc777 => Notification::Osc777 {
+            title: "promptly".to_owned(),
+            body,
+        },
+    }
+}
+
 fn format_date_time() -> Div {
     let current_time = Local::now();
     let mut div = Div::new_empty();
@@ -249,24 +903,58 @@ fn format_date_time() -> Div {
     div
 }
 
-fn format_user_host() -> Div {
-    let username = match get_current_username() {
-        None => "<unknown_user>".to_owned(),
-        Some(un) => un.into_string().unwrap_or_else(|_| "<unknown_user>".into()),
-    };
-    let hostname = get()
+fn format_disk_usage(usage: &filesystem::DiskUsage) -> Div {
+    let mut div = Div::new(
+        Span::new(&filesystem::format_size(usage.available))
+            .foreground(Color::Red)
+            .bold(),
+    );
+    div.add_span(Span::new(" free ").foreground(Color::Red).dimmed());
+    div.add_span(Span::new(&format!("{}%", usage.used_percent)).foreground(Color::Red));
+    div.add_span(Span::new(" used").foreground(Color::Red).dimmed());
+    div
+}
+
+fn format_alert_banner(banner: &str, color: Color) -> Div {
+    Div::new(Span::new(banner).foreground(color).bold().reverse())
+}
+
+fn find_hostname() -> String {
+    get()
         .ok()
         .to_owned()
         .map(|s| s.to_string_lossy().to_string())
-        .unwrap_or_else(|| "<unknown_host>".to_owned());
-    let mut span = Span::new(&username);
-    span = match get_effective_uid() {
-        0 => span.foreground(Color::Red).bold(),
-        _ => span.foreground(Color::Blue).dimmed(),
-    };
-    let mut div = Div::new(span);
-    div.add_span(Span::new("@").foreground(Color::White).dimmed());
-    div.add_span(Span::new(&hostname).foreground(Color::Green).dimmed());
+        .unwrap_or_else(|| "<unknown_host>".to_owned())
+}
+
+fn format_user_host(
+    identity: &identity::Identity,
+    hostname: Option<&str>,
+    addresses: &[network::InterfaceAddress],
+    session: &session::Session,
+) -> Div {
+    let mut div = Div::new_empty();
+    let icons = session.icons();
+    if !icons.is_empty() {
+        for icon in icons {
+            div.add_span(icon);
+        }
+        div.add_span(Span::new(" "));
+    }
+    for span in identity.spans() {
+        div.add_span(span);
+    }
+    if let Some(hostname) = hostname {
+        div.add_span(Span::new("@").foreground(Color::White).dimmed());
+        div.add_span(Span::new(hostname).foreground(Color::Green).dimmed());
+    }
+    if !addresses.is_empty() {
+        div.add_span(match hostname {
+            Some(_) => Span::new(" "),
+            None => Span::new("@").foreground(Color::White).dimmed(),
+        });
+        network::add_address_spans(&mut div, addresses);
+    }
     div
 }
 
@@ -274,6 +962,7 @@ fn format_user_host() -> Div {
 mod tests {
     use super::Layout;
     use super::*;
+    use crate::scratch::ScratchDir;
 
     fn format_runs(runs: &[Run]) -> Vec<String> {
         runs.iter()
@@ -304,6 +993,98 @@ mod tests {
         // }
     }
 
+    #[test]
+    fn run_time_units() {
+        let fmt = |d| format_run_time(d).text();
+        assert_eq!(fmt(Duration::ZERO), "ε");
+        assert_eq!(fmt(Duration::from_millis(250)), "250ms");
+        assert_eq!(fmt(Duration::from_millis(2540)), "2.5s");
+        assert_eq!(fmt(Duration::from_secs(60)), "1m");
+        assert_eq!(fmt(Duration::from_secs(3600)), "1h");
+        assert_eq!(fmt(Duration::from_secs(3661)), "1h1m1s");
+        assert_eq!(fmt(Duration::from_secs(86400 + 120)), "1d2m");
+    }
+
+    #[test]
+    fn shell_state_counters() {
+        let fmt = |jobs, stopped, dirstack, level| {
+            format_shell_state(jobs, stopped, dirstack, level)
+                .iter()
+                .map(|div| div.text())
+                .collect::<Vec<String>>()
+        };
+        assert!(fmt(0, 0, 0, 1).is_empty());
+        assert!(fmt(0, 0, 0, 0).is_empty());
+        assert_eq!(fmt(2, 0, 0, 1), ["⚙2"]);
+        assert_eq!(fmt(0, 1, 0, 1), ["⏸1"]);
+        assert_eq!(fmt(0, 0, 3, 1), ["≡3"]);
+        assert_eq!(fmt(0, 0, 0, 2), ["↳2"]);
+        assert_eq!(fmt(1, 2, 3, 4), ["⚙1", "⏸2", "≡3", "↳4"]);
+    }
+
+    #[test]
+    fn web_urls() {
+        assert_eq!(
+            remote_to_web_url("git@github.com:terrence2/promptly.git").as_deref(),
+            Some("https://github.com/terrence2/promptly")
+        );
+        assert_eq!(
+            remote_to_web_url("ssh://git@gitlab.example.com:2222/group/repo").as_deref(),
+            Some("https://gitlab.example.com/group/repo")
+        );
+        assert_eq!(
+            remote_to_web_url("https://github.com/terrence2/promptly.git").as_deref(),
+            Some("https://github.com/terrence2/promptly")
+        );
+        assert_eq!(remote_to_web_url("file:///srv/git/repo"), None);
+    }
+
+    #[test]
+    fn logical_paths() {
+        let root = ScratchDir::new("pwd");
+        let real = root.join("real");
+        let link = root.join("link");
+        std::fs::create_dir(&real).unwrap();
+        std::os::unix::fs::symlink(&real, &link).unwrap();
+        assert_eq!(logical_path(&real, Some(link.clone())), link);
+        assert_eq!(logical_path(&real, Some(root.to_path_buf())), real);
+        assert_eq!(
+            logical_path(&real, Some(link.join("..").join("link"))),
+            real
+        );
+        assert_eq!(logical_path(&real, None), real);
+    }
+
+    #[test]
+    fn links_take_no_width() {
+        let options = LayoutOptions::new()
+            .width(40)
+            .use_color(false)
+            .use_safe_corners(true)
+            .escape_for_readline(false);
+        let url = format!(
+            "https://example.com/tree/{}",
+            percent_encode(b"fix/$(id) #1")
+        );
+        assert_eq!(url, "https://example.com/tree/fix/%24%28id%29%20%231");
+        let left = vec![
+            Div::new(Span::new("AAAA").link(&url)),
+            Div::new(Span::new("BBBB")),
+        ];
+        let layout = Layout::build(Div::new(Span::new("TTT")), left, vec![], &options).unwrap();
+        assert_eq!(
+            format_runs(&Run::render_layout(&layout)),
+            &[
+                "┬──────┬──────┬───────────────────┐ TTT ",
+                &format!(
+                    "├ \x1B]8;;{}\x07AAAA\x1B]8;;\x07 ┴ BBBB ┘                   └─────",
+                    url
+                ),
+                "└➤ ",
+            ]
+        );
+    }
+
     #[test]
     fn single_line() {
         do_test(
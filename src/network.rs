@@ -0,0 +1,212 @@
+/*
+ * Promptly: show a prompt, sooner.
+ * Copyright (C) 2017  Terrence Cole
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+use crate::{
+    alert::glob_match,
+    layout::{Color, Div, Span},
+};
+use std::{
+    ffi::CStr,
+    mem::MaybeUninit,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    str::FromStr,
+};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct InterfaceAddress {
+    pub interface: String,
+    pub address: IpAddr,
+}
+
+// Either an interface name, which may be a glob like `eth*`, or a network in
+// CIDR notation like `10.0.0.0/8`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum AddressFilter {
+    Interface(String),
+    Network(IpAddr, u8),
+}
+
+impl FromStr for AddressFilter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some((network, prefix)) = s.split_once('/') else {
+            return Ok(AddressFilter::Interface(s.to_owned()));
+        };
+        let network = network
+            .parse::<IpAddr>()
+            .map_err(|_| format!("invalid network address in '{}'", s))?;
+        let max_prefix = if network.is_ipv4() { 32 } else { 128 };
+        let prefix = prefix
+            .parse::<u8>()
+            .ok()
+            .filter(|p| *p <= max_prefix)
+            .ok_or_else(|| format!("invalid prefix length in '{}'", s))?;
+        Ok(AddressFilter::Network(network, prefix))
+    }
+}
+
+impl AddressFilter {
+    pub fn matches(&self, addr: &InterfaceAddress) -> bool {
+        match self {
+            AddressFilter::Interface(pattern) => glob_match(pattern, &addr.interface),
+            AddressFilter::Network(network, prefix) => in_network(&addr.address, network, *prefix),
+        }
+    }
+}
+
+fn in_network(address: &IpAddr, network: &IpAddr, prefix: u8) -> bool {
+    let mask = |bits: u32| -> u128 {
+        match prefix as u32 {
+            0 => 0,
+            p => u128::MAX << (bits - p),
+        }
+    };
+    match (address, network) {
+        (IpAddr::V4(a), IpAddr::V4(n)) => {
+            let mask = mask(32) as u32;
+            u32::from(*a) & mask == u32::from(*n) & mask
+        }
+        (IpAddr::V6(a), IpAddr::V6(n)) => {
+            let mask = mask(128);
+            u128::from(*a) & mask == u128::from(*n) & mask
+        }
+        _ => false,
+    }
+}
+
+// Link-local v6 addresses exist on every interface and cannot be reached
+// without naming the interface too, so they are never what you want to ssh to.
+fn is_link_local(address: &IpAddr) -> bool {
+    match address {
+        IpAddr::V4(_) => false,
+        IpAddr::V6(v6) => v6.segments()[0] & 0xffc0 == 0xfe80,
+    }
+}
+
+// Every address on an interface that is up and is not loopback, in the order the
+// kernel lists them. With filters, an address must match at least one.
+pub fn find_addresses(filters: &[AddressFilter]) -> Vec<InterfaceAddress> {
+    let mut head = MaybeUninit::<*mut libc::ifaddrs>::uninit();
+    if unsafe { libc::getifaddrs(head.as_mut_ptr()) } != 0 {
+        return Vec::new();
+    }
+    let head = unsafe { head.assume_init() };
+
+    let mut out = Vec::new();
+    let mut cursor = head;
+    while let Some(ifa) = unsafe { cursor.as_ref() } {
+        cursor = ifa.ifa_next;
+        let flags = ifa.ifa_flags as libc::c_int;
+        if flags & libc::IFF_UP == 0 || flags & libc::IFF_LOOPBACK != 0 {
+            continue;
+        }
+        let Some(address) = (unsafe { sockaddr_to_ip(ifa.ifa_addr) }) else {
+            continue;
+        };
+        let interface = unsafe { CStr::from_ptr(ifa.ifa_name) }
+            .to_string_lossy()
+            .to_string();
+        let addr = InterfaceAddress { interface, address };
+        if !is_link_local(&addr.address)
+            && (filters.is_empty() || filters.iter().any(|f| f.matches(&addr)))
+        {
+            out.push(addr);
+        }
+    }
+    unsafe { libc::freeifaddrs(head) };
+    out
+}
+
+unsafe fn sockaddr_to_ip(sa: *const libc::sockaddr) -> Option<IpAddr> {
+    let family = unsafe { sa.as_ref()? }.sa_family as libc::c_int;
+    match family {
+        libc::AF_INET => {
+            let sin = unsafe { &*(sa as *const libc::sockaddr_in) };
+            Some(IpAddr::V4(Ipv4Addr::from(u32::from_be(
+                sin.sin_addr.s_addr,
+            ))))
+        }
+        libc::AF_INET6 => {
+            let sin6 = unsafe { &*(sa as *const libc::sockaddr_in6) };
+            Some(IpAddr::V6(Ipv6Addr::from(sin6.sin6_addr.s6_addr)))
+        }
+        _ => None,
+    }
+}
+
+// The standard library already writes v6 in its shortest form.
+pub fn add_address_spans(div: &mut Div, addresses: &[InterfaceAddress]) {
+    for (i, addr) in addresses.iter().enumerate() {
+        if i > 0 {
+            div.add_span(Span::new(",").foreground(Color::White).dimmed());
+        }
+        let color = if addr.address.is_ipv4() {
+            Color::Cyan
+        } else {
+            Color::Blue
+        };
+        div.add_span(Span::new(&addr.address.to_string()).foreground(color));
+    }
+}
+
+pub fn format_addresses(addresses: &[InterfaceAddress]) -> Div {
+    let mut div = Div::new_empty();
+    add_address_spans(&mut div, addresses);
+    div
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addr(interface: &str, address: &str) -> InterfaceAddress {
+        InterfaceAddress {
+            interface: interface.to_owned(),
+            address: address.parse().unwrap(),
+        }
+    }
+
+    #[test]
+    fn filters() {
+        let lan = "192.168.1.0/24".parse::<AddressFilter>().unwrap();
+        assert!(lan.matches(&addr("eth0", "192.168.1.20")));
+        assert!(!lan.matches(&addr("eth0", "192.168.2.20")));
+        assert!(!lan.matches(&addr("eth0", "fd00::1")));
+
+        let ula = "fd00::/8".parse::<AddressFilter>().unwrap();
+        assert!(ula.matches(&addr("wlan0", "fd12:3456::1")));
+        assert!(!ula.matches(&addr("wlan0", "2001:db8::1")));
+
+        let any = "0.0.0.0/0".parse::<AddressFilter>().unwrap();
+        assert!(any.matches(&addr("eth0", "8.8.8.8")));
+
+        let eth = "eth*".parse::<AddressFilter>().unwrap();
+        assert!(eth.matches(&addr("eth1", "10.0.0.1")));
+        assert!(!eth.matches(&addr("docker0", "172.17.0.1")));
+
+        assert!("10.0.0.0/33".parse::<AddressFilter>().is_err());
+        assert!("nonsense/8".parse::<AddressFilter>().is_err());
+    }
+
+    #[test]
+    fn link_local() {
+        assert!(is_link_local(&"fe80::1".parse().unwrap()));
+        assert!(!is_link_local(&"fd00::1".parse().unwrap()));
+        assert!(!is_link_local(&"169.254.0.1".parse().unwrap()));
+    }
+}
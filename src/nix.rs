@@ -0,0 +1,185 @@
+/*
+ * Promptly: show a prompt, sooner.
+ * Copyright (C) 2017  Terrence Cole
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+use crate::{
+    filesystem,
+    layout::{Color, Div, Span},
+};
+use std::{
+    env::{var, var_os},
+    path::{Path, PathBuf},
+};
+
+#[derive(Debug, PartialEq)]
+pub enum NixShell {
+    Pure,
+    Impure,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Direnv {
+    Loaded,
+    // There is an .envrc above us that direnv has not loaded, most likely
+    // because it has not been allowed yet.
+    Blocked,
+}
+
+pub struct DevEnv {
+    pub nix_shell: Option<NixShell>,
+    pub name: Option<String>,
+    pub direnv: Option<Direnv>,
+}
+
+// `nix develop` and `nix-shell` export IN_NIX_SHELL, and the derivation's
+// attributes, including its name, as plain variables.
+fn find_nix_shell(
+    in_nix_shell: Option<&str>,
+    name: Option<&str>,
+) -> (Option<NixShell>, Option<String>) {
+    let shell = match in_nix_shell {
+        Some("pure") => Some(NixShell::Pure),
+        Some(_) => Some(NixShell::Impure),
+        None => return (None, None),
+    };
+    // mkShell without a name calls itself nix-shell, which says nothing.
+    let name = name
+        .filter(|n| !n.is_empty() && *n != "nix-shell")
+        .map(|n| n.to_owned());
+    (shell, name)
+}
+
+// The .envrc direnv says it loaded: DIRENV_FILE, or on older versions the
+// directory in DIRENV_DIR, which it prefixes with a dash.
+fn loaded_envrc() -> Option<PathBuf> {
+    var_os("DIRENV_FILE").map(PathBuf::from).or_else(|| {
+        let dir = var("DIRENV_DIR").ok()?;
+        Some(Path::new(dir.trim_start_matches('-')).join(".envrc"))
+    })
+}
+
+// direnv exports nothing about an .envrc it refuses to load, but while it has one
+// loaded, it loads the nearest. If that is not the one it says it loaded, the
+// nearer one was refused. Compare by inode, as $PWD may have come by a symlink.
+fn find_direnv(loaded: Option<&Path>, nearest: Option<&Path>) -> Option<Direnv> {
+    let loaded = loaded?;
+    match nearest {
+        Some(nearest) if !filesystem::is_same_file(nearest, loaded) => Some(Direnv::Blocked),
+        _ => Some(Direnv::Loaded),
+    }
+}
+
+pub fn find_dev_env(cwd: &Path, check_blocked: bool) -> Option<DevEnv> {
+    let (nix_shell, name) = find_nix_shell(
+        var("IN_NIX_SHELL").ok().as_deref(),
+        var("name").ok().as_deref(),
+    );
+    let loaded = loaded_envrc();
+    let nearest = match (&loaded, check_blocked) {
+        (Some(_), true) => cwd
+            .ancestors()
+            .map(|dir| dir.join(".envrc"))
+            .find(|path| path.is_file()),
+        _ => None,
+    };
+    let direnv = find_direnv(loaded.as_deref(), nearest.as_deref());
+    if nix_shell.is_none() && direnv.is_none() {
+        return None;
+    }
+    Some(DevEnv {
+        nix_shell,
+        name,
+        direnv,
+    })
+}
+
+pub fn format_dev_env(env: &DevEnv) -> Div {
+    let mut div = Div::new_empty();
+    if let Some(shell) = &env.nix_shell {
+        div.add_span(match shell {
+            NixShell::Pure => Span::new("❄").foreground(Color::Cyan).bold(),
+            NixShell::Impure => Span::new("❄").foreground(Color::Blue),
+        });
+        if let Some(name) = &env.name {
+            div.add_span(Span::new(name).foreground(Color::Blue).bold());
+        }
+    }
+    if let Some(direnv) = &env.direnv {
+        if env.nix_shell.is_some() {
+            div.add_span(Span::new(" "));
+        }
+        div.add_span(match direnv {
+            Direnv::Loaded => Span::new("envrc").foreground(Color::Green).dimmed(),
+            Direnv::Blocked => Span::new("envrc✗").foreground(Color::Red).bold(),
+        });
+    }
+    div
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scratch::ScratchDir;
+    use std::{
+        fs::{create_dir, write},
+        os::unix::fs::symlink,
+    };
+
+    #[test]
+    fn nix_shells() {
+        assert_eq!(find_nix_shell(None, Some("dev")), (None, None));
+        assert_eq!(
+            find_nix_shell(Some("pure"), Some("promptly-dev")),
+            (Some(NixShell::Pure), Some("promptly-dev".to_owned()))
+        );
+        assert_eq!(
+            find_nix_shell(Some("impure"), Some("nix-shell")),
+            (Some(NixShell::Impure), None)
+        );
+        assert_eq!(
+            find_nix_shell(Some("1"), None),
+            (Some(NixShell::Impure), None)
+        );
+    }
+
+    #[test]
+    fn direnv_states() {
+        let root = ScratchDir::new("direnv");
+        let outer = root.join(".envrc");
+        let inner = root.join("inner").join(".envrc");
+        create_dir(root.join("inner")).unwrap();
+        write(&outer, "use flake\n").unwrap();
+        write(&inner, "export FOO=1\n").unwrap();
+        symlink(&root, root.join("link")).unwrap();
+
+        assert_eq!(find_direnv(None, Some(&outer)), None);
+        assert_eq!(find_direnv(Some(&outer), None), Some(Direnv::Loaded));
+        assert_eq!(
+            find_direnv(Some(&outer), Some(&outer)),
+            Some(Direnv::Loaded)
+        );
+        assert_eq!(
+            find_direnv(Some(&outer), Some(&inner)),
+            Some(Direnv::Blocked)
+        );
+        // direnv names the file by the path we came by.
+        let logical = root.join("link").join(".envrc");
+        assert_eq!(
+            find_direnv(Some(&logical), Some(&outer)),
+            Some(Direnv::Loaded)
+        );
+    }
+}
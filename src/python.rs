@@ -0,0 +1,176 @@
+/*
+ * Promptly: show a prompt, sooner.
+ * Copyright (C) 2017  Terrence Cole
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+use crate::layout::{Color, Div, Span};
+use std::{
+    env::var,
+    fs::{read_dir, read_to_string},
+    path::Path,
+};
+
+pub struct PythonEnv {
+    pub name: String,
+    pub version: Option<String>,
+}
+
+// Look at the environment variables that venv, conda and pyenv export. We never
+// run python to ask for its version; that alone would cost more than the prompt.
+pub fn find_python_env() -> Option<PythonEnv> {
+    if let Ok(venv) = var("VIRTUAL_ENV") {
+        return Some(find_virtualenv(Path::new(&venv)));
+    }
+    if let Ok(name) = var("CONDA_DEFAULT_ENV") {
+        let version = var("CONDA_PREFIX")
+            .ok()
+            .and_then(|prefix| find_conda_python_version(Path::new(&prefix)));
+        return Some(PythonEnv { name, version });
+    }
+    if let Ok(pyenv) = var("PYENV_VERSION") {
+        // Either a python version, like 3.12.1, or the name of a pyenv-virtualenv.
+        return Some(if pyenv.starts_with(|c: char| c.is_ascii_digit()) {
+            PythonEnv {
+                name: "pyenv".to_owned(),
+                version: Some(pyenv),
+            }
+        } else {
+            PythonEnv {
+                name: pyenv,
+                version: None,
+            }
+        });
+    }
+    None
+}
+
+fn find_virtualenv(venv: &Path) -> PythonEnv {
+    // Newer venvs export the name the activate script would put in PS1.
+    let prompt = var("VIRTUAL_ENV_PROMPT").ok().map(|p| {
+        p.trim()
+            .trim_start_matches('(')
+            .trim_end_matches(')')
+            .to_owned()
+    });
+    let name = match prompt {
+        Some(p) if !p.is_empty() => p,
+        // A venv named .venv or venv says nothing; use the project it lives in.
+        _ => {
+            let base = venv.file_name().map(|n| n.to_string_lossy().to_string());
+            match base.as_deref() {
+                Some(".venv") | Some("venv") | None => venv
+                    .parent()
+                    .and_then(|p| p.file_name())
+                    .map(|n| n.to_string_lossy().to_string())
+                    .unwrap_or_else(|| "venv".to_owned()),
+                Some(b) => b.to_owned(),
+            }
+        }
+    };
+    PythonEnv {
+        name,
+        version: read_pyvenv_version(&venv.join("pyvenv.cfg")),
+    }
+}
+
+// pyvenv.cfg is a list of `key = value` lines. The stdlib venv module writes
+// `version`, while virtualenv and uv write `version_info`, sometimes in the long
+// form of sys.version_info, e.g. 3.11.4.final.0.
+fn read_pyvenv_version(cfg: &Path) -> Option<String> {
+    let content = read_to_string(cfg).ok()?;
+    for line in content.lines() {
+        if let Some((key, value)) = line.split_once('=')
+            && matches!(key.trim(), "version" | "version_info")
+        {
+            let parts = value
+                .trim()
+                .split('.')
+                .take_while(|part| part.chars().all(|c| c.is_ascii_digit()))
+                .take(3)
+                .collect::<Vec<&str>>();
+            if !parts.is_empty() {
+                return Some(parts.join("."));
+            }
+        }
+    }
+    None
+}
+
+// Conda records each installed package in conda-meta as NAME-VERSION-BUILD.json.
+fn find_conda_python_version(prefix: &Path) -> Option<String> {
+    for entry in read_dir(prefix.join("conda-meta")).ok()?.flatten() {
+        let file_name = entry.file_name().to_string_lossy().to_string();
+        if let Some(rest) = file_name.strip_prefix("python-")
+            && rest.starts_with(|c: char| c.is_ascii_digit())
+        {
+            return rest.split('-').next().map(|v| v.to_owned());
+        }
+    }
+    None
+}
+
+pub fn format_python_env(env: &PythonEnv) -> Div {
+    let mut div = Div::new_empty();
+    div.add_span(Span::new("@").foreground(Color::Yellow));
+    div.add_span(Span::new("py").foreground(Color::Cyan));
+    div.add_span(Span::new("{").bold());
+    div.add_span(Span::new(&env.name).foreground(Color::Yellow).bold());
+    if let Some(version) = &env.version {
+        div.add_span(Span::new(" "));
+        div.add_span(Span::new(version).foreground(Color::Green).dimmed());
+    }
+    div.add_span(Span::new("}").bold());
+    div
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scratch::ScratchDir;
+    use std::fs::{create_dir_all, write};
+
+    #[test]
+    fn venv_and_conda_versions() {
+        let root = ScratchDir::new("python");
+        let cfg = |name: &str, content: &str| {
+            let path = root.join(name);
+            write(&path, content).unwrap();
+            read_pyvenv_version(&path)
+        };
+        assert_eq!(
+            cfg(
+                "venv.cfg",
+                "home = /usr/bin\ninclude-system-site-packages = false\nversion = 3.12.1\n",
+            )
+            .as_deref(),
+            Some("3.12.1")
+        );
+        assert_eq!(
+            cfg("virtualenv.cfg", "version_info = 3.11.4.final.0\n").as_deref(),
+            Some("3.11.4")
+        );
+        assert_eq!(cfg("bare.cfg", "home = /usr/bin\n"), None);
+
+        let meta = root.join("conda").join("conda-meta");
+        create_dir_all(&meta).unwrap();
+        write(meta.join("python-dateutil-2.8.2-pyhd3eb1b0_0.json"), "{}").unwrap();
+        write(meta.join("python-3.11.4-h955ad1f_0.json"), "{}").unwrap();
+        assert_eq!(
+            find_conda_python_version(&root.join("conda")).as_deref(),
+            Some("3.11.4")
+        );
+        assert_eq!(find_conda_python_version(&root), None);
+    }
+}
@@ -22,7 +22,35 @@ enum Format {
     Clear,
     Border,
     Prompt,
-    Span(String),
+    Span(String, Option<String>),
+}
+
+// A desktop notification or bell to send along with the prompt, e.g. after a long
+// build. It goes to stderr rather than into the prompt itself: bash redraws PS1
+// on ^L, window resizes and completion listings, and would notify again each time.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Notification {
+    Bell,
+    Osc9(String),
+    Osc777 { title: String, body: String },
+}
+
+impl Notification {
+    pub fn format(&self, in_tmux: bool) -> String {
+        let mut seq = match self {
+            Notification::Bell => "\x07".to_owned(),
+            Notification::Osc9(body) => format!("\x1B]9;{}\x07", body),
+            Notification::Osc777 { title, body } => {
+                format!("\x1B]777;notify;{};{}\x07", title, body)
+            }
+        };
+        // Tmux swallows OSC sequences unless they are wrapped for passthrough,
+        // with every ESC in the payload doubled. It handles the bell itself.
+        if in_tmux && *self != Notification::Bell {
+            seq = format!("\x1BPtmux;{}\x1B\\", seq.replace('\x1B', "\x1B\x1B"));
+        }
+        seq
+    }
 }
 
 #[derive(Debug)]
@@ -107,7 +135,10 @@ impl Run {
     fn add_span(&mut self, span: &Span, escape_for_readline: bool) {
         self.add_formatted(
             &span.content,
-            Format::Span(span.format_style(escape_for_readline)),
+            Format::Span(
+                span.format_style(escape_for_readline),
+                span.get_link().map(|url| url.to_owned()),
+            ),
         );
     }
 
@@ -189,7 +220,23 @@ impl Run {
 
     pub fn format(&self, escape_for_readline: bool) -> String {
         let mut out = "".to_owned();
+        let mut open_link: Option<&str> = None;
         for (ch, maybe_fmt) in self.cells.iter().zip(self.formats.iter()) {
+            if let Some(fmt) = maybe_fmt {
+                let link = match fmt {
+                    Format::Span(_, link) => link.as_deref(),
+                    _ => None,
+                };
+                if link != open_link {
+                    if open_link.is_some() {
+                        out += &Span::format_link(None, escape_for_readline);
+                    }
+                    if link.is_some() {
+                        out += &Span::format_link(link, escape_for_readline);
+                    }
+                    open_link = link;
+                }
+            }
             if self.use_color {
                 for fmt in maybe_fmt.iter() {
                     out += &Span::get_reset_style(escape_for_readline);
@@ -197,33 +244,42 @@ impl Run {
                         Format::Clear => {}
                         Format::Border => out += &self.border_format,
                         Format::Prompt => out += &self.prompt_format,
-                        Format::Span(s) => out += s,
+                        Format::Span(s, _) => out += s,
                     }
                 }
             }
-            if !self.use_safe_corners {
-                out.push(match *ch {
-                    '┌' => '╭',
-                    '└' => '╰',
-                    '┐' => '╮',
-                    '┘' => '╯',
-                    c => c,
-                });
-            } else {
-                out.push(*ch);
-            }
+            let ch = match *ch {
+                '┌' if !self.use_safe_corners => '╭',
+                '└' if !self.use_safe_corners => '╰',
+                '┐' if !self.use_safe_corners => '╮',
+                '┘' if !self.use_safe_corners => '╯',
+                c => c,
+            };
+            out += &Span::escape_text(ch.encode_utf8(&mut [0; 4]), escape_for_readline);
+        }
+        if open_link.is_some() {
+            out += &Span::format_link(None, escape_for_readline);
         }
         out
     }
 
-    pub fn show(&self, escape_for_readline: bool) {
-        println!("{}", self.format(escape_for_readline));
+    // What goes to stdout, and so into PS1.
+    pub fn format_all(runs: &[Run], escape_for_readline: bool) -> String {
+        runs.iter()
+            .map(|run| run.format(escape_for_readline) + "\n")
+            .collect()
     }
 
-    pub fn show_all(runs: &[Run], escape_for_readline: bool) {
-        for run in runs {
-            run.show(escape_for_readline);
+    pub fn show_all(
+        runs: &[Run],
+        notification: Option<&Notification>,
+        in_tmux: bool,
+        escape_for_readline: bool,
+    ) {
+        if let Some(notification) = notification {
+            eprint!("{}", notification.format(in_tmux));
         }
+        print!("{}", Self::format_all(runs, escape_for_readline));
     }
 
     pub fn render_layout(layout: &Layout) -> Vec<Self> {
@@ -350,3 +406,64 @@ impl Run {
         runs
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::LayoutOptions;
+
+    #[test]
+    fn notification_tmux_passthrough() {
+        let osc9 = Notification::Osc9("done".to_owned());
+        assert_eq!(osc9.format(false), "\x1B]9;done\x07");
+        assert_eq!(osc9.format(true), "\x1BPtmux;\x1B\x1B]9;done\x07\x1B\\");
+        assert_eq!(Notification::Bell.format(true), "\x07");
+    }
+
+    #[test]
+    fn notification_not_in_prompt() {
+        let options = LayoutOptions::new()
+            .width(40)
+            .use_color(true)
+            .escape_for_readline(true);
+        let layout = Layout::build(
+            Div::new(Span::new("5m")),
+            vec![Div::new(Span::new("~/src").link("file:///src"))],
+            vec![],
+            &options,
+        )
+        .unwrap();
+        let prompt = Run::format_all(&Run::render_layout(&layout), true);
+        assert!(!prompt.contains("\x1B]9;"), "{:?}", prompt);
+        assert!(!prompt.contains("\x1B]777;"), "{:?}", prompt);
+        assert!(!prompt.contains("\x1BPtmux;"), "{:?}", prompt);
+        // The only BELs are the ones ending the hyperlink.
+        assert_eq!(
+            prompt.matches('\x07').count(),
+            prompt.matches("\x1B]8;;").count(),
+            "{:?}",
+            prompt
+        );
+    }
+
+    #[test]
+    fn readline_escaping() {
+        assert_eq!(Span::escape_text("a$b", false), "a$b");
+        assert_eq!(Span::escape_text("a\\b`id`$x", true), r"a\\\\b\\`id\\`\\$x");
+
+        // Text that reaches the prompt cannot expand when bash reads PS1.
+        let options = LayoutOptions::new()
+            .width(40)
+            .use_color(false)
+            .use_safe_corners(true)
+            .escape_for_readline(true);
+        let spans = vec![
+            Div::new(Span::new("$(touch${IFS}/tmp/pwned)")),
+            Div::new(Span::new("a\\b`id`")),
+        ];
+        let layout = Layout::build(Div::new(Span::new("T")), spans, vec![], &options).unwrap();
+        let out = Run::format_all(&Run::render_layout(&layout), true);
+        assert!(out.contains(r"\\$(touch\\${IFS}/tmp/pwned)"), "{}", out);
+        assert!(out.contains(r"a\\\\b\\`id\\`"), "{}", out);
+    }
+}
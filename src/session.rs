@@ -0,0 +1,123 @@
+/*
+ * Promptly: show a prompt, sooner.
+ * Copyright (C) 2017  Terrence Cole
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+use crate::layout::{Color, Span};
+use std::{env::var, fs::read_to_string, path::Path};
+
+// Where this shell is running, as far as the environment and a few well known
+// files can tell us.
+#[derive(Debug, Default)]
+pub struct Session {
+    pub ssh: bool,
+    pub container: Option<String>,
+    pub chroot: Option<String>,
+    pub wsl: bool,
+}
+
+impl Session {
+    pub fn find() -> Self {
+        Session {
+            ssh: ["SSH_CONNECTION", "SSH_CLIENT", "SSH_TTY"]
+                .iter()
+                .any(|name| var(name).is_ok()),
+            container: find_container(),
+            chroot: find_chroot(),
+            wsl: var("WSL_DISTRO_NAME").is_ok()
+                || read_to_string("/proc/version")
+                    .is_ok_and(|v| v.to_lowercase().contains("microsoft")),
+        }
+    }
+
+    // WSL is a local session; it just happens to be Linux on Windows.
+    pub fn is_local(&self) -> bool {
+        !self.ssh && self.container.is_none() && self.chroot.is_none()
+    }
+
+    pub fn icons(&self) -> Vec<Span> {
+        let mut icons = Vec::new();
+        if self.ssh {
+            icons.push(Span::new("⇌").foreground(Color::Yellow).bold());
+        }
+        if let Some(container) = &self.container {
+            icons.push(Span::new("⬢").foreground(Color::Purple).bold());
+            icons.push(Span::new(container).foreground(Color::Purple));
+        }
+        if let Some(chroot) = &self.chroot {
+            icons.push(Span::new("⛓").foreground(Color::Cyan).bold());
+            icons.push(Span::new(chroot).foreground(Color::Cyan));
+        }
+        if self.wsl {
+            icons.push(Span::new("⊞").foreground(Color::Blue).bold());
+        }
+        icons
+    }
+}
+
+// Name the container if we can, otherwise say what kind it is.
+fn find_container() -> Option<String> {
+    // distrobox exports the container name directly.
+    if let Ok(name) = var("CONTAINER_ID") {
+        return Some(name);
+    }
+    // podman, and toolbox on top of it, describe the container here.
+    if let Ok(content) = read_to_string("/run/.containerenv") {
+        let kind = if Path::new("/run/.toolboxenv").exists() {
+            "toolbox"
+        } else {
+            "podman"
+        };
+        return Some(parse_containerenv_name(&content).unwrap_or_else(|| kind.to_owned()));
+    }
+    if Path::new("/.dockerenv").exists() {
+        return Some("docker".to_owned());
+    }
+    // systemd-nspawn, lxc and friends set container= for pid 1.
+    var("container").ok().filter(|c| !c.is_empty())
+}
+
+// Lines like `name="fedora-toolbox-39"`.
+fn parse_containerenv_name(content: &str) -> Option<String> {
+    content
+        .lines()
+        .find_map(|line| line.strip_prefix("name="))
+        .map(|name| name.trim().trim_matches('"').to_owned())
+        .filter(|name| !name.is_empty())
+}
+
+// Debian's default bashrc shows the same thing.
+fn find_chroot() -> Option<String> {
+    var("debian_chroot")
+        .ok()
+        .or_else(|| read_to_string("/etc/debian_chroot").ok())
+        .map(|name| name.trim().to_owned())
+        .filter(|name| !name.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn containerenv_name() {
+        let content = "engine=\"podman-4.9.4\"\nname=\"fedora-toolbox-39\"\nrootless=1\n";
+        assert_eq!(
+            parse_containerenv_name(content).as_deref(),
+            Some("fedora-toolbox-39")
+        );
+        assert_eq!(parse_containerenv_name("engine=\"podman\"\n"), None);
+    }
+}
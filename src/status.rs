@@ -0,0 +1,241 @@
+/*
+ * Promptly: show a prompt, sooner.
+ * Copyright (C) 2017  Terrence Cole
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+use crate::layout::Color;
+use std::str::FromStr;
+
+const SIGNALS: [&str; 31] = [
+    "SIGHUP",
+    "SIGINT",
+    "SIGQUIT",
+    "SIGILL",
+    "SIGTRAP",
+    "SIGABRT",
+    "SIGBUS",
+    "SIGFPE",
+    "SIGKILL",
+    "SIGUSR1",
+    "SIGSEGV",
+    "SIGUSR2",
+    "SIGPIPE",
+    "SIGALRM",
+    "SIGTERM",
+    "SIGSTKFLT",
+    "SIGCHLD",
+    "SIGCONT",
+    "SIGSTOP",
+    "SIGTSTP",
+    "SIGTTIN",
+    "SIGTTOU",
+    "SIGURG",
+    "SIGXCPU",
+    "SIGXFSZ",
+    "SIGVTALRM",
+    "SIGPROF",
+    "SIGWINCH",
+    "SIGIO",
+    "SIGPWR",
+    "SIGSYS",
+];
+
+// 128 + SIGPIPE.
+const SIGPIPE_STATUS: i32 = 141;
+
+// From sysexits.h, starting at EX_USAGE (64).
+const SYSEXITS: [&str; 15] = [
+    "EX_USAGE",
+    "EX_DATAERR",
+    "EX_NOINPUT",
+    "EX_NOUSER",
+    "EX_NOHOST",
+    "EX_UNAVAILABLE",
+    "EX_SOFTWARE",
+    "EX_OSERR",
+    "EX_OSFILE",
+    "EX_CANTCREAT",
+    "EX_IOERR",
+    "EX_TEMPFAIL",
+    "EX_PROTOCOL",
+    "EX_NOPERM",
+    "EX_CONFIG",
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StatusClass {
+    Success,
+    // Stopped by the user: ^C, ^\ or ^Z.
+    Interrupted,
+    // Killed by any other signal, e.g. SIGKILL from the OOM killer or a SIGSEGV.
+    Signaled,
+    // The shell could not run the command at all: 126 or 127.
+    NotRun,
+    Failure,
+}
+
+impl StatusClass {
+    pub fn border_color(self) -> Color {
+        match self {
+            StatusClass::Success => Color::Blue,
+            StatusClass::Interrupted => Color::Yellow,
+            StatusClass::Signaled => Color::Purple,
+            StatusClass::NotRun => Color::Cyan,
+            StatusClass::Failure => Color::Red,
+        }
+    }
+}
+
+pub fn classify(code: i32) -> StatusClass {
+    match code {
+        0 => StatusClass::Success,
+        126 | 127 => StatusClass::NotRun,
+        130 | 131 | 148 => StatusClass::Interrupted,
+        c if signal_name(c).is_some() => StatusClass::Signaled,
+        _ => StatusClass::Failure,
+    }
+}
+
+// Shells report death by signal N as 128 + N.
+pub fn signal_name(code: i32) -> Option<&'static str> {
+    if code > 128 {
+        SIGNALS.get((code - 129) as usize).copied()
+    } else {
+        None
+    }
+}
+
+pub fn describe(code: i32) -> Option<&'static str> {
+    match code {
+        126 => Some("not executable"),
+        127 => Some("not found"),
+        64..=78 => Some(SYSEXITS[(code - 64) as usize]),
+        c => signal_name(c),
+    }
+}
+
+// The status of the prior command line: one code per pipeline stage, as given
+// by bash's PIPESTATUS or zsh's pipestatus, or just a single $?.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExitStatus {
+    pub codes: Vec<i32>,
+}
+
+impl ExitStatus {
+    pub fn is_success(&self) -> bool {
+        (0..self.codes.len()).all(|stage| !self.is_failed(stage))
+    }
+
+    // A writer killed by SIGPIPE because its reader finished early, as in
+    // `yes | head`, is how pipelines normally end. Only the last stage has no
+    // reader, so only there is it a failure.
+    pub fn is_failed(&self, stage: usize) -> bool {
+        match self.codes[stage] {
+            0 => false,
+            SIGPIPE_STATUS => stage + 1 == self.codes.len(),
+            _ => true,
+        }
+    }
+
+    // Like `set -o pipefail`, the rightmost failing stage decides.
+    pub fn class(&self) -> StatusClass {
+        (0..self.codes.len())
+            .rev()
+            .find(|&stage| self.is_failed(stage))
+            .map(|stage| classify(self.codes[stage]))
+            .unwrap_or(StatusClass::Success)
+    }
+
+    // PIPESTATUS does not see `!`: after `! false` it holds 1 while $? is 0. When
+    // the two disagree about success, $? is what the shell acted on.
+    pub fn reconcile(self, last_status: i32) -> Self {
+        if (last_status == 0) == self.is_success() {
+            self
+        } else {
+            ExitStatus {
+                codes: vec![last_status],
+            }
+        }
+    }
+
+    pub fn describe_code(code: i32) -> String {
+        match describe(code) {
+            Some(name) => name.to_owned(),
+            None => code.to_string(),
+        }
+    }
+}
+
+impl FromStr for ExitStatus {
+    type Err = String;
+
+    // Accept "0", "0 1 0", "0|141|0" or "0,1".
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let codes = s
+            .split(|c: char| c.is_whitespace() || c == '|' || c == ',')
+            .filter(|part| !part.is_empty())
+            .map(|part| {
+                part.parse::<i32>()
+                    .map_err(|_| format!("invalid exit status '{}'", part))
+            })
+            .collect::<Result<Vec<i32>, String>>()?;
+        if codes.is_empty() {
+            return Err("no exit status given".to_owned());
+        }
+        Ok(ExitStatus { codes })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_codes() {
+        assert_eq!(describe(130), Some("SIGINT"));
+        assert_eq!(describe(137), Some("SIGKILL"));
+        assert_eq!(describe(127), Some("not found"));
+        assert_eq!(describe(64), Some("EX_USAGE"));
+        assert_eq!(describe(78), Some("EX_CONFIG"));
+        assert_eq!(describe(1), None);
+        assert_eq!(describe(128), None);
+    }
+
+    #[test]
+    fn pipeline_class() {
+        let status = "0 141 1".parse::<ExitStatus>().unwrap();
+        assert_eq!(status.codes, vec![0, 141, 1]);
+        assert_eq!(status.class(), StatusClass::Failure);
+        let status = "1|130".parse::<ExitStatus>().unwrap();
+        assert_eq!(status.class(), StatusClass::Interrupted);
+        let status = "141 0".parse::<ExitStatus>().unwrap();
+        assert!(status.is_success());
+        assert_eq!(status.class(), StatusClass::Success);
+        let status = "0 141".parse::<ExitStatus>().unwrap();
+        assert_eq!(status.class(), StatusClass::Signaled);
+        assert!("0,0".parse::<ExitStatus>().unwrap().is_success());
+
+        // `! false`, `! true` and `false | true` under pipefail.
+        let negated = "1".parse::<ExitStatus>().unwrap().reconcile(0);
+        assert!(negated.is_success());
+        assert_eq!(negated.class(), StatusClass::Success);
+        let negated = "0".parse::<ExitStatus>().unwrap().reconcile(1);
+        assert_eq!(negated.codes, vec![1]);
+        assert_eq!(negated.class(), StatusClass::Failure);
+        let pipefail = "1 0".parse::<ExitStatus>().unwrap().reconcile(1);
+        assert_eq!(pipefail.codes, vec![1, 0]);
+        assert!("".parse::<ExitStatus>().is_err());
+    }
+}
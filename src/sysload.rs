@@ -0,0 +1,196 @@
+/*
+ * Promptly: show a prompt, sooner.
+ * Copyright (C) 2017  Terrence Cole
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+use crate::layout::{Color, Div, Span};
+use std::{fs::read_to_string, thread::available_parallelism, time::Duration};
+
+#[derive(Debug, Default, PartialEq)]
+pub struct SysLoad {
+    pub load: Option<f64>,
+    pub cpus: usize,
+    // Percent of memory that is available without swapping.
+    pub memory_available: Option<u64>,
+    pub uptime: Option<Duration>,
+}
+
+// When to start complaining. Each piece doubles as the yellow threshold; red is
+// twice as bad.
+pub struct SysLoadLimits {
+    pub load_per_cpu: f64,
+    pub memory_available: u64,
+    pub uptime: Duration,
+}
+
+pub fn find_sysload() -> SysLoad {
+    SysLoad {
+        load: read_to_string("/proc/loadavg")
+            .ok()
+            .and_then(|s| parse_loadavg(&s)),
+        // This honours cpusets and affinity, which matters in containers.
+        cpus: available_parallelism().map(|n| n.get()).unwrap_or(1),
+        memory_available: read_to_string("/proc/meminfo")
+            .ok()
+            .and_then(|s| parse_meminfo(&s)),
+        uptime: read_to_string("/proc/uptime")
+            .ok()
+            .and_then(|s| parse_uptime(&s)),
+    }
+}
+
+// The one minute average is what tells you whether to start another job now.
+fn parse_loadavg(content: &str) -> Option<f64> {
+    content.split_whitespace().next()?.parse().ok()
+}
+
+fn parse_meminfo(content: &str) -> Option<u64> {
+    let field = |name: &str| -> Option<u64> {
+        content
+            .lines()
+            .find_map(|line| line.strip_prefix(name)?.strip_prefix(':'))?
+            .split_whitespace()
+            .next()?
+            .parse()
+            .ok()
+    };
+    let total = field("MemTotal")?;
+    // Kernels before 3.14 lack MemAvailable; free plus page cache is close.
+    let available = field("MemAvailable")
+        .or_else(|| Some(field("MemFree")? + field("Buffers")? + field("Cached")?))?;
+    (total > 0).then(|| available * 100 / total)
+}
+
+fn parse_uptime(content: &str) -> Option<Duration> {
+    let seconds = content.split_whitespace().next()?.parse::<f64>().ok()?;
+    Duration::try_from_secs_f64(seconds).ok()
+}
+
+enum Level {
+    Normal,
+    Warning,
+    Critical,
+}
+
+impl Level {
+    fn color(&self) -> Option<Color> {
+        match self {
+            Level::Normal => None,
+            Level::Warning => Some(Color::Yellow),
+            Level::Critical => Some(Color::Red),
+        }
+    }
+}
+
+impl SysLoad {
+    fn load_level(&self, limits: &SysLoadLimits) -> Level {
+        match self.load.map(|load| load / self.cpus as f64) {
+            Some(ratio) if ratio >= limits.load_per_cpu * 2.0 => Level::Critical,
+            Some(ratio) if ratio >= limits.load_per_cpu => Level::Warning,
+            _ => Level::Normal,
+        }
+    }
+
+    fn memory_level(&self, limits: &SysLoadLimits) -> Level {
+        match self.memory_available {
+            Some(avail) if avail <= limits.memory_available / 2 => Level::Critical,
+            Some(avail) if avail <= limits.memory_available => Level::Warning,
+            _ => Level::Normal,
+        }
+    }
+
+    // A box that has been up this long is probably missing a kernel update.
+    fn uptime_level(&self, limits: &SysLoadLimits) -> Level {
+        match self.uptime {
+            Some(up) if up >= limits.uptime * 2 => Level::Critical,
+            Some(up) if up >= limits.uptime => Level::Warning,
+            _ => Level::Normal,
+        }
+    }
+}
+
+// Returns nothing at all when every reading is within its limit.
+pub fn format_sysload(sysload: &SysLoad, limits: &SysLoadLimits) -> Option<Div> {
+    let mut div = Div::new_empty();
+    let mut add = |label: &str, value: String, level: Level| {
+        let Some(color) = level.color() else {
+            return;
+        };
+        if div.width() > 0 {
+            div.add_span(Span::new(" "));
+        }
+        div.add_span(Span::new(label).foreground(color.clone()).dimmed());
+        div.add_span(Span::new(&value).foreground(color).bold());
+    };
+    if let Some(load) = sysload.load {
+        add(
+            "load ",
+            format!("{:.1}/{}", load, sysload.cpus),
+            sysload.load_level(limits),
+        );
+    }
+    if let Some(avail) = sysload.memory_available {
+        add("mem ", format!("{}%", avail), sysload.memory_level(limits));
+    }
+    if let Some(up) = sysload.uptime {
+        let up = match up.as_secs() {
+            s if s >= 86400 => format!("{}d", s / 86400),
+            s => format!("{}h", s / 3600),
+        };
+        add("up ", up, sysload.uptime_level(limits));
+    }
+    (div.width() > 0).then_some(div)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_proc_files() {
+        assert_eq!(parse_loadavg("3.52 2.10 1.05 2/811 12345\n"), Some(3.52));
+        assert_eq!(
+            parse_meminfo("MemTotal:  8000000 kB\nMemFree: 100 kB\nMemAvailable:  2000000 kB\n"),
+            Some(25)
+        );
+        assert_eq!(
+            parse_meminfo("MemTotal: 1000 kB\nMemFree: 100 kB\nBuffers: 50 kB\nCached: 50 kB\n"),
+            Some(20)
+        );
+        assert_eq!(
+            parse_uptime("350735.47 234388.90\n"),
+            Some(Duration::from_millis(350_735_470))
+        );
+    }
+
+    #[test]
+    fn hidden_when_normal() {
+        let limits = SysLoadLimits {
+            load_per_cpu: 1.0,
+            memory_available: 10,
+            uptime: Duration::from_secs(30 * 86400),
+        };
+        let mut sysload = SysLoad {
+            load: Some(3.0),
+            cpus: 4,
+            memory_available: Some(50),
+            uptime: Some(Duration::from_secs(86400)),
+        };
+        assert!(format_sysload(&sysload, &limits).is_none());
+        sysload.load = Some(4.5);
+        let div = format_sysload(&sysload, &limits).unwrap();
+        assert_eq!(div.text(), "load 4.5/4");
+    }
+}
@@ -0,0 +1,163 @@
+/*
+ * Promptly: show a prompt, sooner.
+ * Copyright (C) 2017  Terrence Cole
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+use crate::layout::{Color, Div, Span};
+use std::fs::{read_dir, read_to_string};
+
+const THERMAL_DIR: &str = "/sys/class/thermal";
+
+// The Raspberry Pi firmware driver publishes the same bitmask that
+// `vcgencmd get_throttled` prints.
+const PI_THROTTLED: &str = "/sys/devices/platform/soc/soc:firmware/get_throttled";
+
+// Zones that measure the processor itself, rather than a wifi card or the
+// battery, which tend to run at their own temperature.
+const CPU_ZONE_TYPES: &[&str] = &["cpu", "soc", "x86_pkg_temp", "k10temp", "package"];
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Throttled {
+    pub under_voltage: bool,
+    pub capped: bool,
+}
+
+impl Throttled {
+    // Only the low bits describe what is happening now; bits 16 and up record
+    // what has happened since boot, which is not something we can act on.
+    fn from_bits(bits: u32) -> Self {
+        Throttled {
+            under_voltage: bits & 0x1 != 0,
+            capped: bits & (0x2 | 0x4 | 0x8) != 0,
+        }
+    }
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Thermal {
+    // Degrees Celsius.
+    pub temperature: Option<f64>,
+    pub throttled: Throttled,
+}
+
+pub fn find_thermal() -> Option<Thermal> {
+    let zones = read_dir(THERMAL_DIR)
+        .ok()
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter(|entry| {
+            entry
+                .file_name()
+                .to_string_lossy()
+                .starts_with("thermal_zone")
+        })
+        .filter_map(|entry| {
+            let path = entry.path();
+            let kind = read_to_string(path.join("type")).unwrap_or_default();
+            let millis = read_to_string(path.join("temp")).ok()?;
+            Some((kind.trim().to_owned(), millis.trim().parse::<i64>().ok()?))
+        })
+        .collect::<Vec<(String, i64)>>();
+    let throttled = read_to_string(PI_THROTTLED)
+        .ok()
+        .and_then(|s| parse_throttled(&s))
+        .unwrap_or_default();
+    let thermal = Thermal {
+        temperature: hottest_cpu_zone(&zones),
+        throttled,
+    };
+    (thermal != Thermal::default()).then_some(thermal)
+}
+
+// The firmware prints the mask in hex, with or without a leading 0x.
+fn parse_throttled(content: &str) -> Option<Throttled> {
+    let hex = content.trim();
+    let hex = hex.strip_prefix("0x").unwrap_or(hex);
+    u32::from_str_radix(hex, 16).ok().map(Throttled::from_bits)
+}
+
+// Zones report millidegrees. Some drivers report nonsense when a sensor is
+// asleep, so ignore anything at or below freezing.
+fn hottest_cpu_zone(zones: &[(String, i64)]) -> Option<f64> {
+    let plausible = zones.iter().filter(|(_, millis)| *millis > 0);
+    let is_cpu = |kind: &str| {
+        let kind = kind.to_lowercase();
+        CPU_ZONE_TYPES.iter().any(|t| kind.contains(t))
+    };
+    plausible
+        .clone()
+        .filter(|(kind, _)| is_cpu(kind))
+        .map(|(_, millis)| *millis)
+        .max()
+        .or_else(|| plausible.map(|(_, millis)| *millis).max())
+        .map(|millis| millis as f64 / 1000.0)
+}
+
+pub fn format_thermal(thermal: &Thermal, warning: f64) -> Div {
+    let mut div = Div::new_empty();
+    if let Some(temperature) = thermal.temperature {
+        let span = Span::new(&format!("{:.0}°C", temperature));
+        div.add_span(match temperature {
+            t if t >= warning + 10.0 => span.foreground(Color::Red).bold(),
+            t if t >= warning => span.foreground(Color::Yellow).bold(),
+            _ => span.foreground(Color::Green).dimmed(),
+        });
+    }
+    if thermal.throttled.capped {
+        div.add_span(Span::new("⚠").foreground(Color::Red).bold());
+    }
+    if thermal.throttled.under_voltage {
+        div.add_span(Span::new("↯").foreground(Color::Yellow).bold());
+    }
+    div
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn throttled_bits() {
+        assert_eq!(parse_throttled("0x0\n"), Some(Throttled::default()));
+        assert_eq!(
+            parse_throttled("50000"),
+            Some(Throttled {
+                under_voltage: false,
+                capped: false,
+            })
+        );
+        assert_eq!(
+            parse_throttled("0x50005"),
+            Some(Throttled {
+                under_voltage: true,
+                capped: true,
+            })
+        );
+        assert_eq!(parse_throttled("nope"), None);
+    }
+
+    #[test]
+    fn prefers_cpu_zones() {
+        let zones = [
+            ("iwlwifi_1".to_owned(), 90_000),
+            ("x86_pkg_temp".to_owned(), 55_000),
+            ("acpitz".to_owned(), -273_000),
+        ];
+        assert_eq!(hottest_cpu_zone(&zones), Some(55.0));
+        assert_eq!(hottest_cpu_zone(&zones[..1]), Some(90.0));
+        assert_eq!(hottest_cpu_zone(&zones[2..]), None);
+    }
+}
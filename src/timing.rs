@@ -0,0 +1,175 @@
+/*
+ * Promptly: show a prompt, sooner.
+ * Copyright (C) 2017  Terrence Cole
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+use crate::filesystem::{create_runtime_dir, read_private_file, write_private_file};
+use failure::Fallible;
+use std::{
+    fs::{read_to_string, remove_file},
+    io,
+    mem::MaybeUninit,
+    path::PathBuf,
+    time::Duration,
+};
+
+const UNITS: &[(&str, f64)] = &[
+    ("ms", 0.001),
+    ("s", 1.0),
+    ("m", 60.0),
+    ("h", 3600.0),
+    ("d", 86400.0),
+];
+
+// Accept either a plain number of seconds, which may be fractional, or a
+// duration string made of number and unit pairs, e.g. "250ms", "1h2m3s", "2d".
+pub fn parse_duration(s: &str) -> Result<Duration, String> {
+    let s = s.trim();
+    if s.is_empty() {
+        return Err("empty duration".to_owned());
+    }
+    if let Ok(seconds) = s.parse::<f64>() {
+        return Duration::try_from_secs_f64(seconds).map_err(|e| e.to_string());
+    }
+
+    let mut seconds = 0f64;
+    let mut rest = s;
+    while !rest.is_empty() {
+        let number_end = rest
+            .find(|c: char| !c.is_ascii_digit() && c != '.')
+            .ok_or_else(|| format!("missing unit after '{}'", rest))?;
+        let (number, tail) = rest.split_at(number_end);
+        let unit_end = tail
+            .find(|c: char| c.is_ascii_digit() || c == '.')
+            .unwrap_or(tail.len());
+        let (unit, tail) = tail.split_at(unit_end);
+        let value = number
+            .parse::<f64>()
+            .map_err(|_| format!("invalid duration '{}'", s))?;
+        let scale = UNITS
+            .iter()
+            .find(|(name, _)| *name == unit)
+            .map(|(_, scale)| scale)
+            .ok_or_else(|| format!("unknown unit '{}' in '{}'; use ms, s, m, h or d", unit, s))?;
+        seconds += value * scale;
+        rest = tail;
+    }
+    Duration::try_from_secs_f64(seconds).map_err(|e| e.to_string())
+}
+
+fn start_file(shell_pid: u32) -> io::Result<PathBuf> {
+    Ok(create_runtime_dir()?.join(format!("start-{}", shell_pid)))
+}
+
+// Unlike the wall clock, CLOCK_BOOTTIME does not jump when NTP or the user
+// sets the time, and unlike CLOCK_MONOTONIC it keeps counting through suspend.
+fn now_nanos() -> Option<u128> {
+    let mut ts = MaybeUninit::<libc::timespec>::uninit();
+    if unsafe { libc::clock_gettime(libc::CLOCK_BOOTTIME, ts.as_mut_ptr()) } != 0 {
+        return None;
+    }
+    let ts = unsafe { ts.assume_init() };
+    Some(u128::try_from(ts.tv_sec).ok()? * 1_000_000_000 + u128::try_from(ts.tv_nsec).ok()?)
+}
+
+// Field 22 of /proc/<pid>/stat, the process start time in clock ticks since
+// boot. The command name in field 2 may hold spaces and parentheses, so count
+// from the last ')'.
+fn parse_start_time(stat: &str) -> Option<u64> {
+    let (_, rest) = stat.rsplit_once(')')?;
+    rest.split_whitespace().nth(22 - 3)?.parse().ok()
+}
+
+fn shell_start_time(shell_pid: u32) -> Option<u64> {
+    parse_start_time(&read_to_string(format!("/proc/{}/stat", shell_pid)).ok()?)
+}
+
+// Called from the shell's preexec hook, just before a command runs.
+pub fn record_start(shell_pid: u32) -> Fallible<()> {
+    let now = now_nanos().ok_or_else(io::Error::last_os_error)?;
+    let shell_start = shell_start_time(shell_pid).unwrap_or(0);
+    write_private_file(&start_file(shell_pid)?, &format!("{} {}", shell_start, now))?;
+    Ok(())
+}
+
+// A record is `<shell start time> <nanoseconds>`. If the shell that wrote it has
+// exited and its PID been reused, the start times differ and it is not ours.
+fn elapsed_since(record: &str, shell_start: u64, now: u128) -> Option<Duration> {
+    let (recorded_start, started) = record.trim().split_once(' ')?;
+    if recorded_start.parse::<u64>().ok()? != shell_start {
+        return None;
+    }
+    let elapsed = now.checked_sub(started.parse::<u128>().ok()?)?;
+    Some(Duration::from_nanos(u64::try_from(elapsed).ok()?))
+}
+
+// Called when rendering: the time since the matching record_start, if any. The
+// record is removed so that an empty command line does not repeat the last time.
+pub fn take_elapsed(shell_pid: u32) -> Option<Duration> {
+    let path = start_file(shell_pid).ok()?;
+    let record = read_private_file(&path).ok();
+    remove_file(&path).ok();
+    elapsed_since(
+        &record?,
+        shell_start_time(shell_pid).unwrap_or(0),
+        now_nanos()?,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_seconds() {
+        assert_eq!(parse_duration("42"), Ok(Duration::from_secs(42)));
+        assert_eq!(parse_duration("0.25"), Ok(Duration::from_millis(250)));
+        assert!(parse_duration("-1").is_err());
+    }
+
+    #[test]
+    fn parse_units() {
+        assert_eq!(parse_duration("250ms"), Ok(Duration::from_millis(250)));
+        assert_eq!(parse_duration("1h2m3s"), Ok(Duration::from_secs(3723)));
+        assert_eq!(parse_duration("2d"), Ok(Duration::from_secs(172800)));
+        assert_eq!(parse_duration("1.5m"), Ok(Duration::from_secs(90)));
+        assert!(parse_duration("3 fortnights").is_err());
+        assert!(parse_duration("").is_err());
+    }
+
+    #[test]
+    fn stat_start_times() {
+        let stat = "4242 (ba) sh (x)) S 1 4242 4242 34816 4242 4194304 1 2 3 4 5 6 7 8 \
+                    20 0 1 0 987654 12345678 900";
+        assert_eq!(parse_start_time(stat), Some(987654));
+        assert_eq!(parse_start_time("4242 (bash) S 1"), None);
+        let own = read_to_string("/proc/self/stat").unwrap();
+        assert!(parse_start_time(&own).is_some());
+    }
+
+    #[test]
+    fn elapsed_records() {
+        let second = 1_000_000_000;
+        assert_eq!(
+            elapsed_since("987654 5000000000\n", 987654, 7 * second),
+            Some(Duration::from_secs(2))
+        );
+        // The PID now belongs to a different shell.
+        assert_eq!(elapsed_since("987654 5000000000", 123, 7 * second), None);
+        // A record from before a reboot.
+        assert_eq!(elapsed_since("987654 5000000000", 987654, second), None);
+        assert_eq!(elapsed_since("5000000000", 987654, 7 * second), None);
+    }
+}
@@ -0,0 +1,310 @@
+/*
+ * Promptly: show a prompt, sooner.
+ * Copyright (C) 2017  Terrence Cole
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+use crate::{
+    keyvalue,
+    layout::{Color, Div, Span},
+};
+use std::{fs::read_to_string, path::Path};
+
+// Each marker file we look for and how to pull (tool, version) pairs out of it.
+// Versions always come from the file itself; we never run the tool.
+struct Marker {
+    file_name: &'static str,
+    parse: fn(&str) -> Vec<(String, String)>,
+}
+
+const MARKERS: &[Marker] = &[
+    Marker {
+        file_name: ".tool-versions",
+        parse: parse_tool_versions,
+    },
+    Marker {
+        file_name: "mise.toml",
+        parse: parse_mise_toml,
+    },
+    Marker {
+        file_name: ".mise.toml",
+        parse: parse_mise_toml,
+    },
+    Marker {
+        file_name: "go.mod",
+        parse: parse_go_mod,
+    },
+    Marker {
+        file_name: ".nvmrc",
+        parse: |s| parse_version_file("node", s),
+    },
+    Marker {
+        file_name: ".node-version",
+        parse: |s| parse_version_file("node", s),
+    },
+    Marker {
+        file_name: "package.json",
+        parse: parse_package_json,
+    },
+    Marker {
+        file_name: ".java-version",
+        parse: |s| parse_version_file("java", s),
+    },
+    Marker {
+        file_name: "pom.xml",
+        parse: parse_pom_xml,
+    },
+    Marker {
+        file_name: ".terraform-version",
+        parse: |s| parse_version_file("tf", s),
+    },
+];
+
+// asdf and mise use plugin names; show the short names people say out loud.
+const ALIASES: &[(&str, &str)] = &[
+    ("nodejs", "node"),
+    ("golang", "go"),
+    ("terraform", "tf"),
+    ("python", "py"),
+    ("ruby", "rb"),
+    ("rust", "rs"),
+];
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Toolchain {
+    pub tool: String,
+    pub version: String,
+}
+
+// Walk up from `cwd` towards, but not including, `stop_at` (usually $HOME, where
+// a global .tool-versions would otherwise show up everywhere). When the same tool
+// is pinned in several places, the nearest pin wins.
+pub fn find_toolchains(cwd: &Path, stop_at: Option<&Path>) -> Vec<Toolchain> {
+    let mut found: Vec<Toolchain> = Vec::new();
+    for dir in cwd.ancestors() {
+        if Some(dir) == stop_at {
+            break;
+        }
+        for marker in MARKERS {
+            let Ok(content) = read_to_string(dir.join(marker.file_name)) else {
+                continue;
+            };
+            for (tool, version) in (marker.parse)(&content) {
+                let tool = ALIASES
+                    .iter()
+                    .find(|(long, _)| *long == tool)
+                    .map(|(_, short)| short.to_string())
+                    .unwrap_or(tool);
+                if is_plain(&tool) && is_plain(&version) && !found.iter().any(|t| t.tool == tool) {
+                    found.push(Toolchain { tool, version });
+                }
+            }
+        }
+    }
+    found
+}
+
+// Tools like `npm:prettier` and versions or ranges like `>=20`, `~1.2.3` or
+// `lts/iron`. Pin files sit in whatever repository was just cloned, and without
+// readline escaping zsh sees the text raw, so anything else is not shown.
+fn is_plain(text: &str) -> bool {
+    !text.is_empty()
+        && text
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || ".+<>=~^*_/:@-".contains(c))
+}
+
+// `nodejs 20.11.0` per line; later words are fallbacks we do not care about.
+fn parse_tool_versions(content: &str) -> Vec<(String, String)> {
+    content
+        .lines()
+        .map(|line| line.split('#').next().unwrap_or(""))
+        .filter_map(|line| {
+            let mut words = line.split_whitespace();
+            Some((words.next()?.to_owned(), words.next()?.to_owned()))
+        })
+        .collect()
+}
+
+fn parse_mise_toml(content: &str) -> Vec<(String, String)> {
+    keyvalue::parse(content)
+        .into_iter()
+        .filter(|e| e.section == "tools")
+        .map(|e| (e.key, e.value))
+        .collect()
+}
+
+// Prefer the `toolchain go1.22.1` line over the minimum `go 1.22` directive.
+fn parse_go_mod(content: &str) -> Vec<(String, String)> {
+    let mut version = None;
+    for line in content.lines() {
+        let mut words = line.split_whitespace();
+        match (words.next(), words.next()) {
+            (Some("toolchain"), Some(v)) => {
+                return vec![("go".to_owned(), v.trim_start_matches("go").to_owned())];
+            }
+            (Some("go"), Some(v)) => version = Some(v.to_owned()),
+            _ => {}
+        }
+    }
+    version.map(|v| ("go".to_owned(), v)).into_iter().collect()
+}
+
+fn parse_version_file(tool: &str, content: &str) -> Vec<(String, String)> {
+    content
+        .lines()
+        .map(|line| line.trim().trim_start_matches('v'))
+        .find(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|v| (tool.to_owned(), v.to_owned()))
+        .into_iter()
+        .collect()
+}
+
+// Only `"engines": { "node": ">=20" }` matters, so look for that rather than
+// parsing the whole document.
+fn parse_package_json(content: &str) -> Vec<(String, String)> {
+    let find_string_value = |s: &str, key: &str| -> Option<String> {
+        let after_key = &s[s.find(&format!("\"{}\"", key))? + key.len() + 2..];
+        let after_colon = after_key.trim_start().strip_prefix(':')?.trim_start();
+        let value = after_colon.strip_prefix('"')?;
+        Some(value[..value.find('"')?].to_owned())
+    };
+    let Some(engines_at) = content.find("\"engines\"") else {
+        return vec![];
+    };
+    let engines = &content[engines_at..];
+    let engines = &engines[..engines.find('}').unwrap_or(engines.len())];
+    find_string_value(engines, "node")
+        .map(|v| ("node".to_owned(), v))
+        .into_iter()
+        .collect()
+}
+
+fn parse_pom_xml(content: &str) -> Vec<(String, String)> {
+    for tag in [
+        "maven.compiler.release",
+        "java.version",
+        "maven.compiler.source",
+    ] {
+        let open = format!("<{}>", tag);
+        if let Some(start) = content.find(&open) {
+            let rest = &content[start + open.len()..];
+            if let Some(end) = rest.find('<') {
+                return vec![("java".to_owned(), rest[..end].trim().to_owned())];
+            }
+        }
+    }
+    vec![]
+}
+
+pub fn format_toolchains(toolchains: &[Toolchain]) -> Div {
+    let mut div = Div::new_empty();
+    for (i, toolchain) in toolchains.iter().enumerate() {
+        if i > 0 {
+            div.add_span(Span::new(" "));
+        }
+        div.add_span(Span::new(&toolchain.tool).foreground(Color::Cyan));
+        div.add_span(
+            Span::new(&toolchain.version)
+                .foreground(Color::Green)
+                .bold(),
+        );
+    }
+    div
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scratch::ScratchDir;
+    use std::fs::write;
+
+    fn pairs(v: &[(&str, &str)]) -> Vec<(String, String)> {
+        v.iter()
+            .map(|(a, b)| (a.to_string(), b.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn parse_markers() {
+        assert_eq!(
+            parse_tool_versions("nodejs 20.11.0 system\n# comment\nterraform 1.6.0\n"),
+            pairs(&[("nodejs", "20.11.0"), ("terraform", "1.6.0")])
+        );
+        assert_eq!(
+            parse_go_mod("module x\n\ngo 1.22\n"),
+            pairs(&[("go", "1.22")])
+        );
+        assert_eq!(
+            parse_go_mod("module x\ngo 1.22\ntoolchain go1.22.1\n"),
+            pairs(&[("go", "1.22.1")])
+        );
+        assert_eq!(
+            parse_package_json(r#"{"name": "x", "engines": { "npm": "10", "node" : ">=20" }}"#),
+            pairs(&[("node", ">=20")])
+        );
+        assert_eq!(parse_package_json(r#"{"node": "20"}"#), vec![]);
+        assert_eq!(
+            parse_pom_xml("<properties><java.version>17</java.version></properties>"),
+            pairs(&[("java", "17")])
+        );
+        assert_eq!(
+            parse_version_file("node", "v20.1.0\n"),
+            pairs(&[("node", "20.1.0")])
+        );
+    }
+
+    #[test]
+    fn plain_versions() {
+        assert!(is_plain("20.11.0"));
+        assert!(is_plain(">=20"));
+        assert!(is_plain("lts/iron"));
+        assert!(is_plain("3.12.1+local_build"));
+        assert!(is_plain("npm:prettier"));
+        assert!(!is_plain(""));
+        assert!(!is_plain("$(touch${IFS}/tmp/pwned)"));
+        assert!(!is_plain("`id`"));
+        assert!(!is_plain("20 && id"));
+    }
+
+    #[test]
+    fn hostile_pins() {
+        let dir = ScratchDir::new("toolchains");
+        write(dir.join(".nvmrc"), "$(touch${IFS}/tmp/pwned)\n").unwrap();
+        write(
+            dir.join(".tool-versions"),
+            "$(id) 1.0\npython 3.12.1\nruby`id` 3.3.0\n",
+        )
+        .unwrap();
+        write(
+            dir.join("mise.toml"),
+            "[tools]\nnode$(id) = \"20\"\ngo = \"1.22\"\n",
+        )
+        .unwrap();
+        let found = find_toolchains(&dir, dir.parent());
+        assert_eq!(
+            found,
+            vec![
+                Toolchain {
+                    tool: "py".to_owned(),
+                    version: "3.12.1".to_owned()
+                },
+                Toolchain {
+                    tool: "go".to_owned(),
+                    version: "1.22".to_owned()
+                },
+            ]
+        );
+    }
+}